@@ -1,9 +1,29 @@
+pub mod bitcrusher;
+pub mod chorus;
+pub mod compressor;
+pub mod delay;
+pub mod distortion;
 pub mod dummy;
 pub mod envelope;
+pub mod envelope_follower;
+pub mod equalizer;
 pub mod filter;
+pub mod flanger;
+pub mod formant_filter;
 pub mod gain;
+pub mod ladder_filter;
+pub mod lfo;
+pub mod limiter;
+pub mod mixer;
+pub mod multi_stage_envelope;
 pub mod oscillator;
+pub mod oversample;
+pub mod phaser;
 pub mod ramp;
+pub mod reverb;
+pub mod ring_modulator;
+pub mod state_variable_filter;
+pub mod vocoder;
 
 use simple_error::SimpleError;
 use crate::midi::messages::MidiData;
@@ -22,19 +42,59 @@ pub trait MidiNode {
 pub enum InputType {
     In,
     Gate,
+    Trigger,
     Detune,
     FilterOffset,
+    Note,
+    Velocity,
+    Envelope,
+    Morph,
+    Sidechain,
+    BitsOffset,
+    RateOffset,
+    Carrier,
+    Modulator,
+    Channel(usize),
 }
 
 #[derive(Debug)]
 pub enum OutputType {
     Out,
-    Gate
+    Gate,
+    Lowpass,
+    Bandpass,
+    Highpass,
+    Notch,
+    Finished,
+    Left,
+    Right,
+    GainReduction,
+    Aux(usize),
 }
 
+pub use bitcrusher::Bitcrusher;
+pub use chorus::Chorus;
+pub use compressor::Compressor;
+pub use delay::Delay;
+pub use distortion::Distortion;
 pub use dummy::Dummy;
 pub use envelope::Envelope;
+pub use envelope_follower::EnvelopeFollower;
+pub use equalizer::Equalizer;
 pub use filter::Filter;
+pub use flanger::Flanger;
+pub use formant_filter::FormantFilter;
 pub use gain::Gain;
+pub use ladder_filter::LadderFilter;
+pub use lfo::Lfo;
+pub use limiter::Limiter;
+pub use mixer::Mixer;
+pub use multi_stage_envelope::MultiStageEnvelope;
 pub use oscillator::OscillatorNode;
+pub use oversample::Oversample;
+pub use phaser::Phaser;
 pub use ramp::Ramp;
+pub use reverb::Reverb;
+pub use ring_modulator::RingModulator;
+pub use state_variable_filter::StateVariableFilter;
+pub use vocoder::Vocoder;
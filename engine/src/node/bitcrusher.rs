@@ -0,0 +1,113 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::SAMPLE_RATE;
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::random::Random;
+
+pub const MIN_BITS: f32 = 1.0;
+pub const MAX_BITS: f32 = 24.0;
+
+/// A lo-fi effect, cutting down the bit depth and sample rate
+///
+/// Both work in fractions, so they can be swept smoothly: 4.5 bits has 2^3.5 steps each side of
+/// 0, and a rate of 1000.5 hz holds each sample for 47.98 samples on average.
+///
+/// # Inputs
+/// `in` - Audio in.
+/// `bits_offset` - Added to the bit depth.
+/// `rate_offset` - Moves the sample rate, in octaves.
+///
+/// # Outputs
+/// `out` - Audio out.
+pub struct Bitcrusher {
+    bits: f32,
+    rate: f32,   // in hertz
+    dither: f32, // between 0 and 1
+    random: Random,
+    hold_phase: f32, // between 0 and 1, takes a new sample on wrapping around
+    held: f32,
+    bits_offset_in: f32,
+    rate_offset_in: f32,
+    input_in: f32,
+    output_out: f32,
+}
+
+impl Bitcrusher {
+    pub fn new(bits: f32, rate: f32) -> Bitcrusher {
+        Bitcrusher {
+            bits: bits.clamp(MIN_BITS, MAX_BITS),
+            rate,
+            dither: 0.0,
+            random: Random::default(),
+            hold_phase: 1.0, // take the first sample straight away
+            held: 0.0,
+            bits_offset_in: 0.0,
+            rate_offset_in: 0.0,
+            input_in: 0_f32,
+            output_out: 0_f32,
+        }
+    }
+
+    pub fn get_bits(&self) -> f32 {
+        self.bits
+    }
+    /// between `MIN_BITS` and `MAX_BITS`
+    pub fn set_bits(&mut self, bits: f32) {
+        self.bits = bits.clamp(MIN_BITS, MAX_BITS);
+    }
+
+    pub fn get_rate(&self) -> f32 {
+        self.rate
+    }
+    /// in hertz, at the sample rate or above leaves the rate alone
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+    }
+
+    pub fn get_dither(&self) -> f32 {
+        self.dither
+    }
+    /// how much noise to add before reducing the bit depth, trading distortion for hiss
+    pub fn set_dither(&mut self, dither: f32) {
+        self.dither = dither.clamp(0.0, 1.0);
+    }
+}
+
+impl AudioNode for Bitcrusher {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            InputType::BitsOffset => self.bits_offset_in = input,
+            InputType::RateOffset => self.rate_offset_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        if self.hold_phase >= 1.0 {
+            self.hold_phase = self.hold_phase.fract();
+            self.held = self.input_in;
+        }
+
+        let rate = self.rate * f32::powf(2.0, self.rate_offset_in);
+        self.hold_phase += rate / SAMPLE_RATE as f32;
+
+        let bits = (self.bits + self.bits_offset_in).clamp(MIN_BITS, MAX_BITS);
+        let steps = f32::powf(2.0, bits - 1.0); // each side of 0
+
+        // triangular dither, spanning one step either way
+        let noise = (self.random.next_f32() - self.random.next_f32()) * self.dither / steps;
+
+        self.output_out = ((self.held + noise) * steps).round() / steps;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
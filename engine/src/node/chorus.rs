@@ -0,0 +1,163 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::{PI, SAMPLE_RATE, TWO_PI};
+use crate::node::lfo::{Lfo, LfoRate, LfoShape};
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::delay_line::DelayLine;
+
+/// delay in the middle of each voice's sweep, in seconds
+const CENTER_DELAY: f32 = 0.015;
+/// how far either side of the center the voices sweep at full depth, in seconds
+const MAX_SWEEP: f32 = 0.005;
+pub const MAX_VOICES: usize = 8;
+
+/// A chorus, thickening the sound with several modulated copies of it
+///
+/// Each voice reads the same delay line through its own lfo, spread evenly around the cycle.
+/// The right side's lfos are a quarter cycle ahead of the left's.
+///
+/// # Inputs
+/// `in` - Audio in.
+///
+/// # Outputs
+/// `out` - Both sides mixed to mono.
+/// `left` - Left side.
+/// `right` - Right side.
+pub struct Chorus {
+    rate: f32, // in hertz
+    depth: f32, // between 0 and 1
+    feedback: f32,
+    mix: f32, // 0 is completely dry, 1 is completely wet
+    line: DelayLine,
+    lfos: Vec<[Lfo; 2]>, // left and right, one pair per voice
+    feedback_sample: f32,
+    input_in: f32,
+    output_out: f32,
+    output_left: f32,
+    output_right: f32,
+}
+
+impl Chorus {
+    pub fn new(rate: f32, depth: f32) -> Chorus {
+        let mut new_chorus = Chorus {
+            rate,
+            depth: depth.clamp(0.0, 1.0),
+            feedback: 0.0,
+            mix: 0.5,
+            line: DelayLine::new(CENTER_DELAY + MAX_SWEEP),
+            lfos: vec![],
+            feedback_sample: 0.0,
+            input_in: 0_f32,
+            output_out: 0_f32,
+            output_left: 0_f32,
+            output_right: 0_f32,
+        };
+
+        new_chorus.set_voices(3);
+
+        new_chorus
+    }
+
+    pub fn get_voices(&self) -> usize {
+        self.lfos.len()
+    }
+
+    /// number of delayed copies, up to `MAX_VOICES`
+    pub fn set_voices(&mut self, voices: usize) {
+        let voices = voices.clamp(1, MAX_VOICES);
+
+        self.lfos = (0..voices)
+            .map(|voice| {
+                let phase = TWO_PI * voice as f32 / voices as f32;
+
+                let mut left = Lfo::new(LfoShape::Sine, self.rate);
+                left.set_phase_offset(phase);
+                let mut right = Lfo::new(LfoShape::Sine, self.rate);
+                right.set_phase_offset(phase + PI / 2.0);
+
+                [left, right]
+            })
+            .collect();
+    }
+
+    pub fn get_rate(&self) -> f32 {
+        self.rate
+    }
+    /// in hertz
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+
+        for lfo in self.lfos.iter_mut().flatten() {
+            lfo.set_rate(LfoRate::Hertz(rate));
+        }
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.depth
+    }
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn get_feedback(&self) -> f32 {
+        self.feedback
+    }
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-0.95, 0.95);
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl AudioNode for Chorus {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let dry = self.input_in;
+
+        self.line.write(dry + self.feedback_sample * self.feedback);
+
+        let mut wet = [0.0; 2];
+        for voice in self.lfos.iter_mut() {
+            for (side, lfo) in voice.iter_mut().enumerate() {
+                lfo.process();
+                let sweep = lfo.get_output_audio(OutputType::Out).unwrap() * self.depth * MAX_SWEEP;
+
+                wet[side] += self.line.read((CENTER_DELAY + sweep) * SAMPLE_RATE as f32);
+            }
+        }
+
+        // keep the level the same however many voices there are
+        let voices = self.lfos.len() as f32;
+        let wet = [wet[0] / voices, wet[1] / voices];
+        self.feedback_sample = (wet[0] + wet[1]) * 0.5;
+
+        let dry_level = 1.0 - self.mix;
+
+        self.output_left = dry * dry_level + wet[0] * self.mix;
+        self.output_right = dry * dry_level + wet[1] * self.mix;
+        self.output_out = (self.output_left + self.output_right) * 0.5;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            OutputType::Left => Ok(self.output_left),
+            OutputType::Right => Ok(self.output_right),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::{from_decibels, smoothing_coefficient, to_decibels};
+
+/// A feed forward compressor, turning down anything louder than the threshold
+///
+/// # Inputs
+/// `in` - Audio in.
+/// `sidechain` - Audio to listen to instead of `in`, when the sidechain is turned on.
+///
+/// # Outputs
+/// `out` - Audio out.
+/// `gain_reduction` - How far the audio is being turned down, in decibels (0 or more).
+pub struct Compressor {
+    threshold: f32, // in decibels
+    ratio: f32,     // input decibels over the threshold for each output decibel
+    knee: f32,      // width in decibels of the soft knee around the threshold
+    attack: f32,    // in seconds
+    release: f32,   // same
+    makeup: f32,    // in decibels
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    use_sidechain: bool,
+    gain_reduction: f32, // in decibels, smoothed
+    input_in: f32,
+    sidechain_in: f32,
+    output_out: f32,
+}
+
+impl Compressor {
+    pub fn new(threshold: f32, ratio: f32) -> Compressor {
+        Compressor {
+            threshold,
+            ratio: ratio.max(1.0),
+            knee: 6.0,
+            attack: 0.01,
+            release: 0.1,
+            makeup: 0.0,
+            attack_coefficient: smoothing_coefficient(0.01),
+            release_coefficient: smoothing_coefficient(0.1),
+            use_sidechain: false,
+            gain_reduction: 0.0,
+            input_in: 0_f32,
+            sidechain_in: 0_f32,
+            output_out: 0_f32,
+        }
+    }
+
+    pub fn get_threshold(&self) -> f32 {
+        self.threshold
+    }
+    /// in decibels
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    pub fn get_ratio(&self) -> f32 {
+        self.ratio
+    }
+    /// 1 does nothing, infinity never lets anything past the threshold
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.max(1.0);
+    }
+
+    pub fn get_knee(&self) -> f32 {
+        self.knee
+    }
+    /// in decibels, 0 for a hard knee
+    pub fn set_knee(&mut self, knee: f32) {
+        self.knee = knee.max(0.0);
+    }
+
+    pub fn get_attack(&self) -> f32 {
+        self.attack
+    }
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack;
+        self.attack_coefficient = smoothing_coefficient(attack);
+    }
+
+    pub fn get_release(&self) -> f32 {
+        self.release
+    }
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release;
+        self.release_coefficient = smoothing_coefficient(release);
+    }
+
+    pub fn get_makeup(&self) -> f32 {
+        self.makeup
+    }
+    /// in decibels, to win back the level lost to compression
+    pub fn set_makeup(&mut self, makeup: f32) {
+        self.makeup = makeup;
+    }
+
+    pub fn get_sidechain(&self) -> bool {
+        self.use_sidechain
+    }
+    /// listens to the `sidechain` input instead of `in`
+    pub fn set_sidechain(&mut self, use_sidechain: bool) {
+        self.use_sidechain = use_sidechain;
+    }
+
+    /// how far a level (in decibels) gets turned down, before smoothing
+    pub fn static_gain_reduction(&self, level: f32) -> f32 {
+        let over = level - self.threshold;
+        let slope = 1.0 - 1.0 / self.ratio;
+
+        if 2.0 * over <= -self.knee {
+            0.0
+        } else if 2.0 * over < self.knee {
+            // quadratic curve joining the two straight lines
+            slope * (over + self.knee / 2.0).powi(2) / (2.0 * self.knee)
+        } else {
+            slope * over
+        }
+    }
+}
+
+impl AudioNode for Compressor {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            InputType::Sidechain => self.sidechain_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let key = if self.use_sidechain {
+            self.sidechain_in
+        } else {
+            self.input_in
+        };
+
+        let target = self.static_gain_reduction(to_decibels(key));
+
+        // turning down more is the attack, letting back up is the release
+        let coefficient = if target > self.gain_reduction {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+
+        self.gain_reduction = target + coefficient * (self.gain_reduction - target);
+
+        self.output_out = self.input_in * from_decibels(self.makeup - self.gain_reduction);
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            OutputType::GainReduction => Ok(self.gain_reduction),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::SAMPLE_RATE;
+use crate::node::filter::FilterType;
+use crate::node::{AudioNode, Filter, InputType, OutputType};
+use crate::util::delay_line::DelayLine;
+use crate::util::tempo::NoteLength;
+
+/// longest delay time, in seconds
+pub const MAX_DELAY: f32 = 4.0;
+
+/// seconds for the delay time to glide to a new value
+const TIME_SMOOTHING: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DelayTime {
+    Seconds(f32),
+    /// one note length, at the tempo set with `set_tempo`
+    Synced(NoteLength),
+}
+
+/// An echo effect
+///
+/// Changing the delay time glides to the new time instead of jumping, which bends the pitch of
+/// the echoes like a tape delay rather than clicking.
+///
+/// # Inputs
+/// `in` - Audio in.
+///
+/// # Outputs
+/// `out` - Dry and wet audio mixed together, with both sides summed in ping-pong mode.
+/// `left` - Left side, the same as `out` unless in ping-pong mode.
+/// `right` - Right side, the same as `out` unless in ping-pong mode.
+pub struct Delay {
+    time: DelayTime,
+    tempo: f32,         // in beats per minute
+    current_delay: f32, // in samples, gliding towards the target time
+    smoothing: f32,
+    feedback: f32,
+    mix: f32, // 0 is completely dry, 1 is completely wet
+    ping_pong: bool,
+    lines: [DelayLine; 2],
+    feedback_filters: [Filter; 2], // in the feedback loop, so every repeat gets darker
+    input_in: f32,
+    output_out: f32,
+    output_left: f32,
+    output_right: f32,
+}
+
+impl Delay {
+    pub fn new(time: DelayTime, feedback: f32) -> Delay {
+        let mut new_delay = Delay {
+            time,
+            tempo: 120.0,
+            current_delay: 0.0,
+            smoothing: f32::exp(-1.0 / (TIME_SMOOTHING * SAMPLE_RATE as f32)),
+            feedback: feedback.clamp(-0.99, 0.99),
+            mix: 0.5,
+            ping_pong: false,
+            lines: [DelayLine::new(MAX_DELAY), DelayLine::new(MAX_DELAY)],
+            feedback_filters: [
+                Filter::new(FilterType::Lowpass, 12000.0, 0.707),
+                Filter::new(FilterType::Lowpass, 12000.0, 0.707),
+            ],
+            input_in: 0_f32,
+            output_out: 0_f32,
+            output_left: 0_f32,
+            output_right: 0_f32,
+        };
+
+        // start at the right time instead of gliding up from nothing
+        new_delay.current_delay = new_delay.target_delay();
+
+        new_delay
+    }
+
+    /// current time in seconds, taking tempo sync into account
+    pub fn get_seconds(&self) -> f32 {
+        let seconds = match self.time {
+            DelayTime::Seconds(seconds) => seconds,
+            DelayTime::Synced(note_length) => note_length.seconds(self.tempo),
+        };
+
+        seconds.clamp(0.0, MAX_DELAY)
+    }
+
+    fn target_delay(&self) -> f32 {
+        self.get_seconds() * SAMPLE_RATE as f32
+    }
+
+    pub fn get_time(&self) -> DelayTime {
+        self.time
+    }
+    pub fn set_time(&mut self, time: DelayTime) {
+        self.time = time;
+    }
+
+    pub fn get_tempo(&self) -> f32 {
+        self.tempo
+    }
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.tempo = tempo;
+    }
+
+    pub fn get_feedback(&self) -> f32 {
+        self.feedback
+    }
+    /// how much of each echo comes back around, kept below 1 so it can't run away
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-0.99, 0.99);
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    pub fn get_ping_pong(&self) -> bool {
+        self.ping_pong
+    }
+    /// bounces the echoes between the left and right outputs
+    pub fn set_ping_pong(&mut self, ping_pong: bool) {
+        self.ping_pong = ping_pong;
+        self.lines[1].clear();
+    }
+
+    /// the filters in the feedback loop (left and right), lowpass by default
+    pub fn get_feedback_filters_mut(&mut self) -> &mut [Filter; 2] {
+        &mut self.feedback_filters
+    }
+
+    /// sets up both feedback filters the same way
+    pub fn set_feedback_filter(&mut self, filter_type: FilterType, frequency: f32, q: f32) {
+        for filter in self.feedback_filters.iter_mut() {
+            filter.set_filter_type(filter_type);
+            filter.set_frequency(frequency);
+            filter.set_q(q);
+        }
+    }
+
+    fn filter_feedback(&mut self, side: usize, sample: f32) -> f32 {
+        let filter = &mut self.feedback_filters[side];
+
+        filter.receive_audio(InputType::In, sample).unwrap();
+        filter.process();
+        filter.get_output_audio(OutputType::Out).unwrap()
+    }
+}
+
+impl AudioNode for Delay {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let target = self.target_delay();
+        self.current_delay = target + self.smoothing * (self.current_delay - target);
+
+        // the newest sample gets written after reading, so it's one sample further back
+        let read_delay = (self.current_delay - 1.0).max(0.0);
+        let dry = self.input_in;
+
+        let (wet_left, wet_right) = if self.ping_pong {
+            let left = self.lines[0].read(read_delay);
+            let right = self.lines[1].read(read_delay);
+
+            // the input only goes in on the left, then each side feeds the other
+            let to_left = dry + self.filter_feedback(1, right) * self.feedback;
+            let to_right = self.filter_feedback(0, left) * self.feedback;
+            self.lines[0].write(to_left);
+            self.lines[1].write(to_right);
+
+            (left, right)
+        } else {
+            let wet = self.lines[0].read(read_delay);
+
+            let to_line = dry + self.filter_feedback(0, wet) * self.feedback;
+            self.lines[0].write(to_line);
+
+            (wet, wet)
+        };
+
+        let dry_level = 1.0 - self.mix;
+
+        self.output_left = dry * dry_level + wet_left * self.mix;
+        self.output_right = dry * dry_level + wet_right * self.mix;
+
+        self.output_out = if self.ping_pong {
+            dry * dry_level + (wet_left + wet_right) * self.mix
+        } else {
+            self.output_left
+        };
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            OutputType::Left => Ok(self.output_left),
+            OutputType::Right => Ok(self.output_right),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
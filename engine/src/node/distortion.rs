@@ -0,0 +1,264 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::node::filter::FilterType;
+use crate::node::oversample::OversampleFactor;
+use crate::node::{AudioNode, Filter, InputType, OutputType, Oversample};
+use crate::util::{from_decibels, lerp};
+
+/// how much of the previous output the dc blocker keeps, about a 40hz highpass
+const DC_BLOCKER_POLE: f32 = 0.995;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaperCurve {
+    /// tanh, rounding off smoothly towards 1
+    SoftClip,
+    /// flat at 1, harsh and buzzy
+    HardClip,
+    /// reflects anything past 1 back down, getting brighter the harder it's driven
+    Foldback,
+    /// clips the negative side earlier and softer than the positive side, adding even harmonics
+    Tube,
+    /// output levels spaced evenly from an input of -1 to 1, read with linear interpolation
+    Table(Vec<f32>),
+}
+
+impl ShaperCurve {
+    pub fn apply(&self, input: f32) -> f32 {
+        match self {
+            ShaperCurve::SoftClip => input.tanh(),
+            ShaperCurve::HardClip => input.clamp(-1.0, 1.0),
+            ShaperCurve::Foldback => 1.0 - ((input + 1.0).rem_euclid(4.0) - 2.0).abs(),
+            ShaperCurve::Tube => {
+                if input >= 0.0 {
+                    input.tanh()
+                } else {
+                    0.5 * (input * 2.0).tanh()
+                }
+            }
+            ShaperCurve::Table(table) => {
+                if table.len() < 2 {
+                    return table.first().copied().unwrap_or(input);
+                }
+
+                let position = (input.clamp(-1.0, 1.0) + 1.0) * 0.5 * (table.len() - 1) as f32;
+                let index = (position as usize).min(table.len() - 2);
+
+                lerp(table[index], table[index + 1], position - index as f32)
+            }
+        }
+    }
+}
+
+/// Bends audio through a transfer curve, with no gain or filtering around it
+///
+/// # Inputs
+/// `in` - Audio in.
+///
+/// # Outputs
+/// `out` - Audio out.
+pub struct Waveshaper {
+    curve: ShaperCurve,
+    input_in: f32,
+    output_out: f32,
+}
+
+impl Waveshaper {
+    pub fn new(curve: ShaperCurve) -> Waveshaper {
+        Waveshaper {
+            curve,
+            input_in: 0_f32,
+            output_out: 0_f32,
+        }
+    }
+
+    pub fn get_curve(&self) -> &ShaperCurve {
+        &self.curve
+    }
+    pub fn set_curve(&mut self, curve: ShaperCurve) {
+        self.curve = curve;
+    }
+}
+
+impl AudioNode for Waveshaper {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        self.output_out = self.curve.apply(self.input_in);
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
+
+enum ShaperStage {
+    Direct(Waveshaper),
+    Oversampled(Oversample<Waveshaper>),
+}
+
+/// A distortion effect: gain, a waveshaper, then a tone filter
+///
+/// Lopsided curves like `Tube` push the signal off center, which the dc blocker takes back out.
+///
+/// # Inputs
+/// `in` - Audio in.
+///
+/// # Outputs
+/// `out` - Audio out.
+pub struct Distortion {
+    pre_gain: f32,  // in decibels
+    post_gain: f32, // same
+    shaper: ShaperStage,
+    tone: Filter,
+    dc_correction: bool,
+    dc_prev_input: f32,
+    dc_prev_output: f32,
+    input_in: f32,
+    output_out: f32,
+}
+
+impl Distortion {
+    pub fn new(curve: ShaperCurve, pre_gain: f32) -> Distortion {
+        Distortion {
+            pre_gain,
+            post_gain: 0.0,
+            shaper: ShaperStage::Direct(Waveshaper::new(curve)),
+            tone: Filter::new(FilterType::Lowpass, 8000.0, 0.707),
+            dc_correction: true,
+            dc_prev_input: 0.0,
+            dc_prev_output: 0.0,
+            input_in: 0_f32,
+            output_out: 0_f32,
+        }
+    }
+
+    fn waveshaper(&self) -> &Waveshaper {
+        match &self.shaper {
+            ShaperStage::Direct(shaper) => shaper,
+            ShaperStage::Oversampled(oversample) => oversample.get_node(),
+        }
+    }
+
+    fn waveshaper_mut(&mut self) -> &mut Waveshaper {
+        match &mut self.shaper {
+            ShaperStage::Direct(shaper) => shaper,
+            ShaperStage::Oversampled(oversample) => oversample.get_node_mut(),
+        }
+    }
+
+    pub fn get_curve(&self) -> &ShaperCurve {
+        self.waveshaper().get_curve()
+    }
+    pub fn set_curve(&mut self, curve: ShaperCurve) {
+        self.waveshaper_mut().set_curve(curve);
+    }
+
+    pub fn get_pre_gain(&self) -> f32 {
+        self.pre_gain
+    }
+    /// drive into the curve, in decibels
+    pub fn set_pre_gain(&mut self, pre_gain: f32) {
+        self.pre_gain = pre_gain;
+    }
+
+    pub fn get_post_gain(&self) -> f32 {
+        self.post_gain
+    }
+    /// output level, in decibels
+    pub fn set_post_gain(&mut self, post_gain: f32) {
+        self.post_gain = post_gain;
+    }
+
+    pub fn get_tone(&self) -> f32 {
+        self.tone.get_frequency()
+    }
+    /// cutoff of the lowpass after the curve, taming the fizz
+    pub fn set_tone(&mut self, frequency: f32) {
+        self.tone.set_frequency(frequency);
+    }
+
+    pub fn get_dc_correction(&self) -> bool {
+        self.dc_correction
+    }
+    pub fn set_dc_correction(&mut self, dc_correction: bool) {
+        self.dc_correction = dc_correction;
+    }
+
+    pub fn get_oversampling(&self) -> Option<OversampleFactor> {
+        match &self.shaper {
+            ShaperStage::Direct(_) => None,
+            ShaperStage::Oversampled(oversample) => Some(oversample.get_factor()),
+        }
+    }
+
+    /// runs the curve at a multiple of the sample rate, so harmonics above nyquist don't alias
+    pub fn set_oversampling(&mut self, oversampling: Option<OversampleFactor>) {
+        // briefly swapped for a placeholder, to move the waveshaper out
+        let placeholder = ShaperStage::Direct(Waveshaper::new(ShaperCurve::HardClip));
+
+        let shaper = match std::mem::replace(&mut self.shaper, placeholder) {
+            ShaperStage::Direct(shaper) => shaper,
+            ShaperStage::Oversampled(oversample) => oversample.into_node(),
+        };
+
+        self.shaper = match oversampling {
+            Some(factor) => ShaperStage::Oversampled(Oversample::new(shaper, factor)),
+            None => ShaperStage::Direct(shaper),
+        };
+    }
+}
+
+impl AudioNode for Distortion {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let driven = self.input_in * from_decibels(self.pre_gain);
+
+        let shaper: &mut dyn AudioNode = match &mut self.shaper {
+            ShaperStage::Direct(shaper) => shaper,
+            ShaperStage::Oversampled(oversample) => oversample,
+        };
+
+        shaper.receive_audio(InputType::In, driven).unwrap();
+        shaper.process();
+        let mut output = shaper.get_output_audio(OutputType::Out).unwrap();
+
+        if self.dc_correction {
+            let blocked = output - self.dc_prev_input + DC_BLOCKER_POLE * self.dc_prev_output;
+
+            self.dc_prev_input = output;
+            self.dc_prev_output = blocked;
+            output = blocked;
+        }
+
+        self.tone.receive_audio(InputType::In, output).unwrap();
+        self.tone.process();
+
+        self.output_out = self.tone.get_output_audio(OutputType::Out).unwrap() * from_decibels(self.post_gain);
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
@@ -5,30 +5,131 @@ use crate::constants::SAMPLE_RATE;
 
 use crate::node::{AudioNode, InputType, OutputType};
 
+/// MIDI note that key follow is centered around (middle C)
+pub const KEY_FOLLOW_CENTER: f32 = 60.0;
+
+/// steepest a custom curve can get, any more and the exponentials overflow
+pub const MAX_CURVATURE: f32 = 30.0;
+
+/// Shape of an envelope stage as it moves from its start level to its end level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnvelopeCurve {
+    Linear,
+    /// fast at first, then slowing down, like a capacitor (de)charging
+    Exponential,
+    /// slow at first, then speeding up
+    Logarithmic,
+    /// positive is exponential-like, negative is logarithmic-like, and further from 0 is steeper
+    /// (up to `MAX_CURVATURE` either way)
+    Custom(f32),
+}
+
+impl EnvelopeCurve {
+    /// how far along the stage's level change (0 to 1) the envelope is at `position` (0 to 1)
+    pub fn apply(&self, position: f32) -> f32 {
+        let curvature = match self {
+            EnvelopeCurve::Linear => 0.0,
+            EnvelopeCurve::Exponential => 5.0,
+            EnvelopeCurve::Logarithmic => -5.0,
+            EnvelopeCurve::Custom(curvature) => curvature.clamp(-MAX_CURVATURE, MAX_CURVATURE),
+        };
+
+        let position = position.clamp(0.0, 1.0);
+
+        if curvature.abs() < 0.001 {
+            position
+        } else {
+            (1.0 - f32::exp(-curvature * position)) / (1.0 - f32::exp(-curvature))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EnvelopeState {
     Attacking,
     Decaying,
     Sustaining,
     Releasing,
+    /// released all the way to 0
+    Idle,
 }
 
+/// What a trigger does while the gate is already held
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerMode {
+    /// start the attack over
+    Retrigger,
+    /// ignore it, so overlapping notes glide along the same envelope
+    Legato,
+}
+
+/// Where a new attack starts from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RetriggerStart {
+    /// the current level, so there's no click
+    Continue,
+    /// 0, for consistent attacks
+    Reset,
+}
+
+/// What a voice needs from an envelope, so `Envelope` and `MultiStageEnvelope` can stand in for
+/// each other
+///
+/// Both take the same inputs (`gate`, `trigger`, `note` and `velocity`) and give the same outputs
+/// (`out` and `finished`).
+pub trait EnvelopeGenerator: AudioNode {
+    fn get_velocity_sensitivity(&self) -> f32;
+    /// 0 ignores velocity, 1 scales the envelope by the note's velocity
+    fn set_velocity_sensitivity(&mut self, velocity_sensitivity: f32);
+
+    fn get_trigger_mode(&self) -> TriggerMode;
+    fn set_trigger_mode(&mut self, trigger_mode: TriggerMode);
+
+    /// whether the envelope has released all the way, and its voice is silent
+    fn is_finished(&self) -> bool;
+}
+
+/// An ADSR envelope
+///
+/// # Inputs
+/// `gate` - Attacks while high, releases when low.
+/// `trigger` - Restarts the attack on a rising edge while the gate is held, if retriggering.
+/// `note` - MIDI note number, for key follow.
+/// `velocity` - Between 0 and 1, latched when the attack starts.
+///
+/// # Outputs
+/// `out` - Envelope level.
+/// `finished` - 1 once released all the way, otherwise 0.
 pub struct Envelope {
     attack: f32,
     decay: f32,
     sustain: f32,
     release: f32,
+    attack_curve: EnvelopeCurve,
+    decay_curve: EnvelopeCurve,
+    release_curve: EnvelopeCurve,
+    velocity_sensitivity: f32,        // how much velocity scales the peak level, between 0 and 1
+    velocity_attack_sensitivity: f32, // how much velocity shortens the attack, between 0 and 1
+    key_follow: f32,                  // how much higher notes shorten decay and release
+    peak_level: f32,                  // latched from the velocity when the attack starts
+    attack_scale: f32,                // same
+    trigger_mode: TriggerMode,
+    retrigger_start: RetriggerStart,
     state: EnvelopeState,
     curve_position: f32, // between 0 and 1
     // amplitude_anchor is the spot where the attack is being based on
     // if the note was pressed down again before a complete release, it should attack
     // based on the current amplitude, not jump to 0
     amplitude_anchor: f32, // between 0 and 1
-    current_value: f32,    // between 0 and 1
+    current_value: f32,    // between 0 and 1, before scaling by the peak level
     input_gate: f32,
+    input_trigger: f32,
+    prev_trigger: f32,
+    note_in: f32,
+    velocity_in: f32,
     output_out: f32,
 }
 
-// TODO: ADSR linear only
 impl Envelope {
     pub fn new(attack: f32, decay: f32, sustain: f32, release: f32) -> Envelope {
         Envelope {
@@ -36,25 +137,44 @@ impl Envelope {
             decay,
             sustain,
             release,
-            state: EnvelopeState::Releasing,
+            attack_curve: EnvelopeCurve::Linear,
+            decay_curve: EnvelopeCurve::Linear,
+            release_curve: EnvelopeCurve::Linear,
+            velocity_sensitivity: 0.0,
+            velocity_attack_sensitivity: 0.0,
+            key_follow: 0.0,
+            peak_level: 1.0,
+            attack_scale: 1.0,
+            trigger_mode: TriggerMode::Legato,
+            retrigger_start: RetriggerStart::Continue,
+            state: EnvelopeState::Idle,
             curve_position: 0.0,
             amplitude_anchor: 0.0,
             current_value: 0.0,
             input_gate: 0_f32,
+            input_trigger: 0_f32,
+            prev_trigger: 0_f32,
+            note_in: KEY_FOLLOW_CENTER,
+            velocity_in: 1.0,
             output_out: 0_f32,
         }
     }
 
+    /// how much to multiply decay and release times by for the current note
+    fn key_scale(&self) -> f32 {
+        f32::powf(2.0, -self.key_follow * (self.note_in - KEY_FOLLOW_CENTER) / 12.0)
+    }
+
     fn process_gate_engaged(&mut self) {
         self.state = match &self.state {
             EnvelopeState::Attacking => {
-                let attack_rate = (1.0 / SAMPLE_RATE as f32) / self.attack;
+                let attack_rate = (1.0 / SAMPLE_RATE as f32) / (self.attack * self.attack_scale);
                 self.curve_position += attack_rate;
 
                 // take `self.attack` seconds, even if attack started from not complete release
-                self.current_value = attack(self.amplitude_anchor, 1.0, self.curve_position);
+                self.current_value = attack(self.amplitude_anchor, 1.0, self.curve_position, self.attack_curve);
 
-                if self.current_value >= 1.0 {
+                if self.curve_position >= 1.0 {
                     self.current_value = 1.0;
                     self.curve_position = 0.0; // reset amplitude position for decay
 
@@ -64,12 +184,12 @@ impl Envelope {
                 }
             }
             EnvelopeState::Decaying => {
-                let decay_rate = (1.0 / SAMPLE_RATE as f32) / self.decay;
+                let decay_rate = (1.0 / SAMPLE_RATE as f32) / (self.decay * self.key_scale());
                 self.curve_position += decay_rate;
 
-                self.current_value = decay(1.0, self.sustain, self.curve_position);
+                self.current_value = decay(1.0, self.sustain, self.curve_position, self.decay_curve);
 
-                if self.current_value <= self.sustain {
+                if self.curve_position >= 1.0 {
                     self.current_value = self.sustain;
                     self.curve_position = 0.0; // reset amplitude position for release
 
@@ -83,15 +203,34 @@ impl Envelope {
 
                 EnvelopeState::Sustaining
             }
-            EnvelopeState::Releasing => {
-                self.curve_position = 0.0;
-                self.amplitude_anchor = self.current_value;
+            EnvelopeState::Releasing | EnvelopeState::Idle => {
+                self.start_attack();
 
                 EnvelopeState::Attacking
             }
         }
     }
 
+    fn start_attack(&mut self) {
+        // new note, new velocity, but carry on from the same output level
+        let output = match self.retrigger_start {
+            RetriggerStart::Continue => self.current_value * self.peak_level,
+            RetriggerStart::Reset => 0.0,
+        };
+
+        self.peak_level = 1.0 - self.velocity_sensitivity + self.velocity_sensitivity * self.velocity_in;
+        self.attack_scale = 1.0 - self.velocity_attack_sensitivity * self.velocity_in;
+
+        self.current_value = if self.peak_level > 0.0 {
+            (output / self.peak_level).min(1.0)
+        } else {
+            0.0
+        };
+
+        self.curve_position = 0.0;
+        self.amplitude_anchor = self.current_value;
+    }
+
     fn process_gate_released(&mut self) {
         self.state = match &self.state {
             EnvelopeState::Attacking => {
@@ -114,18 +253,23 @@ impl Envelope {
                 EnvelopeState::Releasing
             }
             EnvelopeState::Releasing => {
-                let release_rate = (1.0 / SAMPLE_RATE as f32) / self.release;
+                let release_rate = (1.0 / SAMPLE_RATE as f32) / (self.release * self.key_scale());
 
                 self.curve_position += release_rate;
 
-                // take `self.attack` seconds, even if attack started from not complete release
-                if self.curve_position <= 1.0 {
-                    self.current_value = release(self.amplitude_anchor, 0.0, self.curve_position);
+                // take `self.release` seconds, even if release started from not complete sustain
+                if self.curve_position < 1.0 {
+                    self.current_value = release(self.amplitude_anchor, 0.0, self.curve_position, self.release_curve);
                     self.current_value = self.current_value.clamp(0.0, 1.0);
-                }
 
-                EnvelopeState::Releasing
+                    EnvelopeState::Releasing
+                } else {
+                    self.current_value = 0.0;
+
+                    EnvelopeState::Idle
+                }
             }
+            EnvelopeState::Idle => EnvelopeState::Idle,
         }
     }
     
@@ -139,12 +283,77 @@ impl Envelope {
         self.sustain = sustain;
         self.release = release;
     }
+
+    pub fn get_velocity_sensitivity(&self) -> f32 {
+        self.velocity_sensitivity
+    }
+
+    /// 0 always peaks at 1, 1 peaks at the note's velocity
+    pub fn set_velocity_sensitivity(&mut self, velocity_sensitivity: f32) {
+        self.velocity_sensitivity = velocity_sensitivity;
+    }
+
+    pub fn get_velocity_attack_sensitivity(&self) -> f32 {
+        self.velocity_attack_sensitivity
+    }
+
+    /// 0 ignores velocity, 1 makes the attack instant at full velocity
+    pub fn set_velocity_attack_sensitivity(&mut self, velocity_attack_sensitivity: f32) {
+        self.velocity_attack_sensitivity = velocity_attack_sensitivity;
+    }
+
+    pub fn get_key_follow(&self) -> f32 {
+        self.key_follow
+    }
+
+    /// 1 halves the decay and release times every octave up (and doubles them every octave down)
+    pub fn set_key_follow(&mut self, key_follow: f32) {
+        self.key_follow = key_follow;
+    }
+
+    pub fn get_trigger_mode(&self) -> TriggerMode {
+        self.trigger_mode
+    }
+
+    pub fn set_trigger_mode(&mut self, trigger_mode: TriggerMode) {
+        self.trigger_mode = trigger_mode;
+    }
+
+    pub fn get_retrigger_start(&self) -> RetriggerStart {
+        self.retrigger_start
+    }
+
+    pub fn set_retrigger_start(&mut self, retrigger_start: RetriggerStart) {
+        self.retrigger_start = retrigger_start;
+    }
+
+    pub fn get_state(&self) -> EnvelopeState {
+        self.state
+    }
+
+    /// whether the envelope has released all the way, and its voice is silent
+    pub fn is_finished(&self) -> bool {
+        self.state == EnvelopeState::Idle
+    }
+
+    pub fn get_curves(&self) -> (EnvelopeCurve, EnvelopeCurve, EnvelopeCurve) {
+        (self.attack_curve, self.decay_curve, self.release_curve)
+    }
+
+    pub fn set_curves(&mut self, attack: EnvelopeCurve, decay: EnvelopeCurve, release: EnvelopeCurve) {
+        self.attack_curve = attack;
+        self.decay_curve = decay;
+        self.release_curve = release;
+    }
 }
 
 impl AudioNode for Envelope {
     fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
         match input_type {
             InputType::Gate => self.input_gate = input,
+            InputType::Trigger => self.input_trigger = input,
+            InputType::Note => self.note_in = input,
+            InputType::Velocity => self.velocity_in = input,
             _ => bail!("Cannot receive {:?}", input_type),
         }
 
@@ -154,33 +363,67 @@ impl AudioNode for Envelope {
     fn process(&mut self) {
         let engaged = self.input_gate > 0.0;
 
+        let triggered = self.input_trigger > 0.0 && self.prev_trigger <= 0.0;
+        self.prev_trigger = self.input_trigger;
+
+        let held = matches!(
+            self.state,
+            EnvelopeState::Attacking | EnvelopeState::Decaying | EnvelopeState::Sustaining
+        );
+
+        if engaged && triggered && held && self.trigger_mode == TriggerMode::Retrigger {
+            self.start_attack();
+            self.state = EnvelopeState::Attacking;
+        }
+
         if engaged {
             self.process_gate_engaged();
         } else {
             self.process_gate_released();
         }
 
-        self.output_out = self.current_value;
+        self.output_out = self.current_value * self.peak_level;
     }
 
     fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
         match output_type {
             OutputType::Out => Ok(self.output_out),
+            OutputType::Finished => Ok(if self.is_finished() { 1.0 } else { 0.0 }),
             _ => bail!("Cannot output {:?}", output_type),
         }
     }
 }
 
-fn attack(start: f32, end: f32, amount: f32) -> f32 {
-    lerp(start, end, amount)
+impl EnvelopeGenerator for Envelope {
+    fn get_velocity_sensitivity(&self) -> f32 {
+        Envelope::get_velocity_sensitivity(self)
+    }
+    fn set_velocity_sensitivity(&mut self, velocity_sensitivity: f32) {
+        Envelope::set_velocity_sensitivity(self, velocity_sensitivity);
+    }
+
+    fn get_trigger_mode(&self) -> TriggerMode {
+        Envelope::get_trigger_mode(self)
+    }
+    fn set_trigger_mode(&mut self, trigger_mode: TriggerMode) {
+        Envelope::set_trigger_mode(self, trigger_mode);
+    }
+
+    fn is_finished(&self) -> bool {
+        Envelope::is_finished(self)
+    }
+}
+
+fn attack(start: f32, end: f32, amount: f32, curve: EnvelopeCurve) -> f32 {
+    lerp(start, end, curve.apply(amount))
 }
 
-fn decay(start: f32, end: f32, amount: f32) -> f32 {
-    lerp(start, end, amount)
+fn decay(start: f32, end: f32, amount: f32, curve: EnvelopeCurve) -> f32 {
+    lerp(start, end, curve.apply(amount))
 }
 
-fn release(start: f32, end: f32, amount: f32) -> f32 {
-    lerp(start, end, amount)
+fn release(start: f32, end: f32, amount: f32, curve: EnvelopeCurve) -> f32 {
+    lerp(start, end, curve.apply(amount))
 }
 
 fn lerp(start: f32, end: f32, amount: f32) -> f32 {
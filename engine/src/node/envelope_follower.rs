@@ -0,0 +1,160 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::{smoothing_coefficient, to_decibels};
+
+/// seconds of input averaged over when detecting rms
+pub const RMS_WINDOW: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Detection {
+    /// follows the absolute value of the input, reacting to transients
+    Peak,
+    /// follows the average power of the input, closer to how loud it sounds
+    Rms,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FollowerScale {
+    /// 0 at silence, 1 at full scale
+    Linear,
+    /// decibels relative to full scale, down to `util::MIN_DECIBELS`
+    Decibels,
+}
+
+/// Turns the level of an audio signal into a control signal
+///
+/// Send the output to a `Filter`'s `envelope` input for an auto-wah, or invert it and send it
+/// to a `Gain`'s `envelope` input for ducking.
+///
+/// # Inputs
+/// `in` - Audio to follow.
+///
+/// # Outputs
+/// `out` - Level of the input.
+pub struct EnvelopeFollower {
+    attack: f32,  // in seconds
+    release: f32, // same
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    detection: Detection,
+    scale: FollowerScale,
+    inverted: bool,
+    rms_coefficient: f32,
+    mean_square: f32,
+    level: f32,
+    input_in: f32,
+    output_out: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(attack: f32, release: f32) -> EnvelopeFollower {
+        EnvelopeFollower {
+            attack,
+            release,
+            attack_coefficient: smoothing_coefficient(attack),
+            release_coefficient: smoothing_coefficient(release),
+            detection: Detection::Peak,
+            scale: FollowerScale::Linear,
+            inverted: false,
+            rms_coefficient: smoothing_coefficient(RMS_WINDOW),
+            mean_square: 0.0,
+            level: 0.0,
+            input_in: 0_f32,
+            output_out: 0_f32,
+        }
+    }
+
+    pub fn get_attack(&self) -> f32 {
+        self.attack
+    }
+
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack;
+        self.attack_coefficient = smoothing_coefficient(attack);
+    }
+
+    pub fn get_release(&self) -> f32 {
+        self.release
+    }
+
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release;
+        self.release_coefficient = smoothing_coefficient(release);
+    }
+
+    pub fn get_detection(&self) -> Detection {
+        self.detection
+    }
+
+    pub fn set_detection(&mut self, detection: Detection) {
+        self.detection = detection;
+    }
+
+    pub fn get_scale(&self) -> FollowerScale {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: FollowerScale) {
+        self.scale = scale;
+    }
+
+    pub fn get_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// outputs 1 minus the linear level instead, for ducking (ignored in decibels)
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    /// current linear level, whatever the output scale
+    pub fn get_level(&self) -> f32 {
+        self.level
+    }
+}
+
+impl AudioNode for EnvelopeFollower {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let detected = match self.detection {
+            Detection::Peak => self.input_in.abs(),
+            Detection::Rms => {
+                let square = self.input_in * self.input_in;
+                self.mean_square = square + self.rms_coefficient * (self.mean_square - square);
+
+                self.mean_square.sqrt()
+            }
+        };
+
+        let coefficient = if detected > self.level {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+
+        self.level = detected + coefficient * (self.level - detected);
+
+        self.output_out = match self.scale {
+            FollowerScale::Linear if self.inverted => (1.0 - self.level).max(0.0),
+            FollowerScale::Linear => self.level,
+            FollowerScale::Decibels => to_decibels(self.level),
+        };
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::node::filter::{FilterType, FrequencyResponse};
+use crate::node::{AudioNode, Filter, InputType, OutputType};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BandType {
+    LowShelf,
+    HighShelf,
+    Peak,
+    Highpass,
+    Lowpass,
+}
+
+impl BandType {
+    fn filter_type(&self) -> FilterType {
+        match self {
+            BandType::LowShelf => FilterType::LowShelf,
+            BandType::HighShelf => FilterType::HighShelf,
+            BandType::Peak => FilterType::Peaking,
+            BandType::Highpass => FilterType::Highpass,
+            BandType::Lowpass => FilterType::Lowpass,
+        }
+    }
+}
+
+/// Settings for one band of an `Equalizer`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqBand {
+    pub band_type: BandType,
+    pub frequency: f32,
+    pub gain: f32, // in decibels, ignored by highpass and lowpass bands
+    pub q: f32,
+}
+
+impl EqBand {
+    pub fn new(band_type: BandType, frequency: f32, gain: f32, q: f32) -> EqBand {
+        EqBand {
+            band_type,
+            frequency,
+            gain,
+            q,
+        }
+    }
+
+    fn to_filter(self) -> Filter {
+        let mut filter = Filter::new(self.band_type.filter_type(), self.frequency, self.q);
+        filter.set_gain(self.gain);
+
+        filter
+    }
+}
+
+/// A parametric equalizer, running any number of bands one after another
+///
+/// # Inputs
+/// `in` - Audio in.
+///
+/// # Outputs
+/// `out` - Audio out.
+pub struct Equalizer {
+    bands: Vec<EqBand>,
+    filters: Vec<Filter>,
+    input_in: f32,
+    output_out: f32,
+}
+
+impl Equalizer {
+    pub fn new(bands: Vec<EqBand>) -> Equalizer {
+        Equalizer {
+            filters: bands.iter().map(|band| band.to_filter()).collect(),
+            bands,
+            input_in: 0_f32,
+            output_out: 0_f32,
+        }
+    }
+
+    pub fn get_bands(&self) -> &[EqBand] {
+        &self.bands
+    }
+
+    pub fn get_band(&self, index: usize) -> Option<&EqBand> {
+        self.bands.get(index)
+    }
+
+    /// changes a band in place, keeping its filter's state so there's no click
+    pub fn set_band(&mut self, index: usize, band: EqBand) -> Result<(), SimpleError> {
+        if index >= self.bands.len() {
+            bail!("No band {}, there are only {}", index, self.bands.len());
+        }
+
+        let filter = &mut self.filters[index];
+        filter.set_filter_type(band.band_type.filter_type());
+        filter.set_frequency(band.frequency);
+        filter.set_gain(band.gain);
+        filter.set_q(band.q);
+
+        self.bands[index] = band;
+
+        Ok(())
+    }
+
+    pub fn add_band(&mut self, band: EqBand) {
+        self.filters.push(band.to_filter());
+        self.bands.push(band);
+    }
+
+    pub fn remove_band(&mut self, index: usize) -> Result<EqBand, SimpleError> {
+        if index >= self.bands.len() {
+            bail!("No band {}, there are only {}", index, self.bands.len());
+        }
+
+        self.filters.remove(index);
+
+        Ok(self.bands.remove(index))
+    }
+}
+
+impl AudioNode for Equalizer {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let mut sample = self.input_in;
+
+        for filter in self.filters.iter_mut() {
+            filter.receive_audio(InputType::In, sample).unwrap();
+            filter.process();
+            sample = filter.get_output_audio(OutputType::Out).unwrap();
+        }
+
+        self.output_out = sample;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
+
+impl FrequencyResponse for Equalizer {
+    /// product of the bands, as they run in series
+    fn complex_response(&self, frequency: f32) -> (f32, f32) {
+        self.filters
+            .iter()
+            .fold((1.0, 0.0), |(real_product, imaginary_product), filter| {
+                let (real, imaginary) = filter.complex_response(frequency);
+
+                (
+                    real_product * real - imaginary_product * imaginary,
+                    real_product * imaginary + imaginary_product * real,
+                )
+            })
+    }
+}
@@ -1,18 +1,117 @@
 use simple_error::bail;
 use simple_error::SimpleError;
 
-use crate::constants::{PI, SAMPLE_RATE};
+use crate::constants::{SAMPLE_RATE, TWO_PI};
 use crate::node::{AudioNode, InputType, OutputType};
 
-#[derive(Clone, Copy)]
+/// MIDI note that key tracking is centered around (middle C)
+pub const KEY_TRACKING_CENTER: f32 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FilterType {
     Lowpass,
+    Highpass,
+    /// peak gain is `q`
+    BandpassConstantSkirt,
+    /// peak gain is 0 dB
+    BandpassConstantPeak,
+    Notch,
+    Allpass,
+    /// boosts or cuts around the frequency by `gain` decibels
+    Peaking,
+    /// boosts or cuts below the frequency by `gain` decibels
+    LowShelf,
+    /// boosts or cuts above the frequency by `gain` decibels
+    HighShelf,
+}
+
+/// A point on a frequency response curve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResponsePoint {
+    pub frequency: f32,
+    pub magnitude: f32, // linear gain
+    pub phase: f32,     // in radians
+}
+
+/// Filters that can report how they affect each frequency, for drawing curves and testing
+pub trait FrequencyResponse {
+    /// response at `frequency` as a complex number (real, imaginary), so filters can be combined
+    fn complex_response(&self, frequency: f32) -> (f32, f32);
+
+    /// linear gain at `frequency`
+    fn magnitude_response(&self, frequency: f32) -> f32 {
+        let (real, imaginary) = self.complex_response(frequency);
+
+        f32::sqrt(real * real + imaginary * imaginary)
+    }
+
+    /// phase shift in radians at `frequency`
+    fn phase_response(&self, frequency: f32) -> f32 {
+        let (real, imaginary) = self.complex_response(frequency);
+
+        f32::atan2(imaginary, real)
+    }
+
+    /// samples the response at `points` logarithmically spaced frequencies
+    fn response_curve(&self, min_frequency: f32, max_frequency: f32, points: usize) -> Vec<ResponsePoint> {
+        (0..points)
+            .map(|i| {
+                let amount = if points > 1 {
+                    i as f32 / (points - 1) as f32
+                } else {
+                    0.0
+                };
+                let frequency = min_frequency * f32::powf(max_frequency / min_frequency, amount);
+
+                ResponsePoint {
+                    frequency,
+                    magnitude: self.magnitude_response(frequency),
+                    phase: self.phase_response(frequency),
+                }
+            })
+            .collect()
+    }
+}
+
+/// normalized so that a0 is 1
+struct BiquadCoefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl BiquadCoefficients {
+    fn complex_response(&self, frequency: f32) -> (f32, f32) {
+        let w = TWO_PI * frequency / SAMPLE_RATE as f32;
+
+        // evaluate the transfer function at z = e^(jw)
+        let numerator_real = self.b0 + self.b1 * w.cos() + self.b2 * (2.0 * w).cos();
+        let numerator_imaginary = -(self.b1 * w.sin() + self.b2 * (2.0 * w).sin());
+        let denominator_real = 1.0 + self.a1 * w.cos() + self.a2 * (2.0 * w).cos();
+        let denominator_imaginary = -(self.a1 * w.sin() + self.a2 * (2.0 * w).sin());
+
+        let denominator_squared =
+            denominator_real * denominator_real + denominator_imaginary * denominator_imaginary;
+
+        (
+            (numerator_real * denominator_real + numerator_imaginary * denominator_imaginary)
+                / denominator_squared,
+            (numerator_imaginary * denominator_real - numerator_real * denominator_imaginary)
+                / denominator_squared,
+        )
+    }
 }
 
 pub struct Filter {
     filter_type: FilterType,
     frequency: f32,
     q: f32,
+    gain: f32, // in decibels, only used by peaking and shelf filters
+    key_tracking: f32, // 1 follows the note's pitch exactly, 0 ignores it
+    envelope_amount: f32, // in octaves at full envelope
+    velocity_sensitivity: f32, // how much velocity scales the envelope amount, between 0 and 1
     dirty: bool,
     a1: f32,
     a2: f32,
@@ -25,6 +124,9 @@ pub struct Filter {
     prev_output_1: f32,
     prev_output_2: f32,
     filter_offset_in: f32,
+    note_in: f32,
+    velocity_in: f32,
+    envelope_in: f32,
     input_in: f32,
     output_out: f32,
 }
@@ -33,6 +135,9 @@ impl AudioNode for Filter {
     fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
         match input_type {
             InputType::FilterOffset => self.filter_offset_in = input,
+            InputType::Note => self.note_in = input,
+            InputType::Velocity => self.velocity_in = input,
+            InputType::Envelope => self.envelope_in = input,
             InputType::In => self.input_in = input,
             _ => bail!("Cannot receive {:?}", input_type),
         }
@@ -41,7 +146,9 @@ impl AudioNode for Filter {
     }
 
     fn process(&mut self) {
-        if f32::abs(self.filter_offset_in - self.prev_offset) > f32::EPSILON || self.dirty {
+        let offset = self.total_offset();
+
+        if f32::abs(offset - self.prev_offset) > f32::EPSILON || self.dirty {
             // avoid excess recomputation
             self.recompute();
         }
@@ -58,7 +165,7 @@ impl AudioNode for Filter {
         self.prev_output_2 = self.prev_output_1;
         self.prev_output_1 = output;
 
-        self.prev_offset = self.filter_offset_in;
+        self.prev_offset = offset;
 
         self.output_out = output;
     }
@@ -77,6 +184,10 @@ impl Filter {
             filter_type,
             frequency,
             q,
+            gain: 0.0,
+            key_tracking: 0.0,
+            envelope_amount: 0.0,
+            velocity_sensitivity: 0.0,
             a1: 0.0,
             a2: 0.0,
             b0: 1.0,
@@ -88,6 +199,9 @@ impl Filter {
             prev_output_1: 0.0,
             prev_output_2: 0.0,
             filter_offset_in: 0.0,
+            note_in: KEY_TRACKING_CENTER,
+            velocity_in: 1.0,
+            envelope_in: 0.0,
             input_in: 0_f32,
             output_out: 0_f32,
             dirty: true,
@@ -98,54 +212,137 @@ impl Filter {
         new_filter
     }
 
+    /// cutoff offset in octaves from all the modulation inputs
+    fn total_offset(&self) -> f32 {
+        let key_offset = self.key_tracking * (self.note_in - KEY_TRACKING_CENTER) / 12.0;
+        let velocity_scale = 1.0 - self.velocity_sensitivity + self.velocity_sensitivity * self.velocity_in;
+
+        self.filter_offset_in + key_offset + self.envelope_in * self.envelope_amount * velocity_scale
+    }
+
     fn recompute(&mut self) {
-        let a1;
-        let a2;
-        let b0;
-        let b1;
-        let b2;
-
-        match &self.filter_type {
-            FilterType::Lowpass => {
-                let freq = (self.frequency * f32::powf(2.0, self.filter_offset_in))
-                    .clamp(0.01, SAMPLE_RATE as f32 * 0.5);
-                //println!("{}", freq);
-
-                let k = (PI * freq / SAMPLE_RATE as f32).tan();
-                let norm = 1.0 / (1.0 + k / self.q + k * k);
-
-                b0 = k * k * norm;
-                b1 = 2.0 * b0;
-                b2 = b0;
-                a1 = 2.0 * (k * k - 1.0) * norm;
-                a2 = (1.0 - k / self.q + k * k) * norm;
-            } // FilterType::Lowpass => {
-              //     // clamp to prevent the filter becoming unstable
-              //     let freq = (self.frequency + (self.filter_offset_in * 10_000.0)).clamp(1.0, SAMPLE_RATE as f32 * 0.5);
-
-              //     let n = 1.0 / (PI * freq / SAMPLE_RATE as f32);
-              //     let n_squared = n * n;
-              //     let inv_q = 1.0 / self.q;
-              //     let c1 = 1.0 / (1.0 + inv_q * n + n_squared);
-
-              //     b0 = c1;
-              //     b1 = c1 * 2.0;
-              //     b2 = c1;
-
-              //     a1 = c1 * 2.0 * (1.0 - n_squared);
-              //     a2 = c1 * (1.0 - inv_q * n + n_squared);
-              // }
-        };
+        let coefficients = self.compute_coefficients();
 
-        self.a1 = a1;
-        self.a2 = a2;
-        self.b0 = b0;
-        self.b1 = b1;
-        self.b2 = b2;
+        self.b0 = coefficients.b0;
+        self.b1 = coefficients.b1;
+        self.b2 = coefficients.b2;
+        self.a1 = coefficients.a1;
+        self.a2 = coefficients.a2;
 
         self.dirty = false;
     }
 
+    fn compute_coefficients(&self) -> BiquadCoefficients {
+        // clamp to prevent the filter becoming unstable
+        let freq = (self.frequency * f32::powf(2.0, self.total_offset()))
+            .clamp(0.01, SAMPLE_RATE as f32 * 0.49);
+
+        // formulas from the RBJ audio EQ cookbook
+        let w0 = TWO_PI * freq / SAMPLE_RATE as f32;
+        let cos_w0 = w0.cos();
+        let alpha = w0.sin() / (2.0 * self.q);
+        let a = f32::powf(10.0, self.gain / 40.0);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+        let (b0, b1, b2, a0, a1, a2) = match &self.filter_type {
+            FilterType::Lowpass => (
+                (1.0 - cos_w0) / 2.0,
+                1.0 - cos_w0,
+                (1.0 - cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::Highpass => (
+                (1.0 + cos_w0) / 2.0,
+                -(1.0 + cos_w0),
+                (1.0 + cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::BandpassConstantSkirt => (
+                self.q * alpha,
+                0.0,
+                -self.q * alpha,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::BandpassConstantPeak => (
+                alpha,
+                0.0,
+                -alpha,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::Notch => (
+                1.0,
+                -2.0 * cos_w0,
+                1.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::Allpass => (
+                1.0 - alpha,
+                -2.0 * cos_w0,
+                1.0 + alpha,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterType::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos_w0,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos_w0,
+                1.0 - alpha / a,
+            ),
+            FilterType::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a_alpha),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                a * ((a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a_alpha),
+                (a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a_alpha,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                (a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a_alpha,
+            ),
+            FilterType::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a_alpha),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                a * ((a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a_alpha),
+                (a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a_alpha,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                (a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a_alpha,
+            ),
+        };
+
+        BiquadCoefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// the coefficients `process` will use, even if parameters changed since it last ran
+    fn current_coefficients(&self) -> BiquadCoefficients {
+        if self.dirty || f32::abs(self.total_offset() - self.prev_offset) > f32::EPSILON {
+            self.compute_coefficients()
+        } else {
+            BiquadCoefficients {
+                b0: self.b0,
+                b1: self.b1,
+                b2: self.b2,
+                a1: self.a1,
+                a2: self.a2,
+            }
+        }
+    }
+
     pub fn get_filter_type(&self) -> FilterType {
         self.filter_type
     }
@@ -169,4 +366,43 @@ impl Filter {
         self.dirty = true;
         self.q = q;
     }
+
+    pub fn get_gain(&self) -> f32 {
+        self.gain
+    }
+    /// in decibels, for the peaking and shelf filter types
+    pub fn set_gain(&mut self, gain: f32) {
+        self.dirty = true;
+        self.gain = gain;
+    }
+
+    pub fn get_key_tracking(&self) -> f32 {
+        self.key_tracking
+    }
+    /// 1 moves the cutoff with the note's pitch, 0.5 moves it half as far
+    pub fn set_key_tracking(&mut self, key_tracking: f32) {
+        self.key_tracking = key_tracking;
+    }
+
+    pub fn get_envelope_amount(&self) -> f32 {
+        self.envelope_amount
+    }
+    /// in octaves, when the envelope input is at 1
+    pub fn set_envelope_amount(&mut self, envelope_amount: f32) {
+        self.envelope_amount = envelope_amount;
+    }
+
+    pub fn get_velocity_sensitivity(&self) -> f32 {
+        self.velocity_sensitivity
+    }
+    /// between 0 (envelope amount ignores velocity) and 1 (envelope amount scales with velocity)
+    pub fn set_velocity_sensitivity(&mut self, velocity_sensitivity: f32) {
+        self.velocity_sensitivity = velocity_sensitivity;
+    }
+}
+
+impl FrequencyResponse for Filter {
+    fn complex_response(&self, frequency: f32) -> (f32, f32) {
+        self.current_coefficients().complex_response(frequency)
+    }
 }
@@ -0,0 +1,144 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::{PI, SAMPLE_RATE};
+use crate::node::lfo::{Lfo, LfoPolarity, LfoRate, LfoShape};
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::delay_line::DelayLine;
+
+/// shortest delay in the sweep, in seconds
+const MIN_DELAY: f32 = 0.0005;
+/// how far the sweep goes up from the shortest delay at full depth, in seconds
+const MAX_SWEEP: f32 = 0.005;
+
+/// A flanger, sweeping a comb filter made from a very short delay
+///
+/// Negative feedback moves the notches to where the peaks were, for a hollower sound. The right
+/// side's lfo is a quarter cycle ahead of the left's.
+///
+/// # Inputs
+/// `in` - Audio in.
+///
+/// # Outputs
+/// `out` - Both sides mixed to mono.
+/// `left` - Left side.
+/// `right` - Right side.
+pub struct Flanger {
+    rate: f32,  // in hertz
+    depth: f32, // between 0 and 1
+    feedback: f32,
+    mix: f32, // 0 is completely dry, 1 is completely wet
+    lines: [DelayLine; 2],
+    lfos: [Lfo; 2],
+    wet: [f32; 2], // last output of each side, for feedback
+    input_in: f32,
+    output_out: f32,
+    output_left: f32,
+    output_right: f32,
+}
+
+impl Flanger {
+    pub fn new(rate: f32, depth: f32) -> Flanger {
+        let mut lfos = [
+            Lfo::new(LfoShape::Triangle, rate),
+            Lfo::new(LfoShape::Triangle, rate),
+        ];
+        lfos[1].set_phase_offset(PI / 2.0);
+
+        for lfo in lfos.iter_mut() {
+            lfo.set_polarity(LfoPolarity::Unipolar);
+        }
+
+        Flanger {
+            rate,
+            depth: depth.clamp(0.0, 1.0),
+            feedback: 0.5,
+            mix: 0.5,
+            lines: [
+                DelayLine::new(MIN_DELAY + MAX_SWEEP),
+                DelayLine::new(MIN_DELAY + MAX_SWEEP),
+            ],
+            lfos,
+            wet: [0.0; 2],
+            input_in: 0_f32,
+            output_out: 0_f32,
+            output_left: 0_f32,
+            output_right: 0_f32,
+        }
+    }
+
+    pub fn get_rate(&self) -> f32 {
+        self.rate
+    }
+    /// in hertz
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+
+        for lfo in self.lfos.iter_mut() {
+            lfo.set_rate(LfoRate::Hertz(rate));
+        }
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.depth
+    }
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn get_feedback(&self) -> f32 {
+        self.feedback
+    }
+    /// between -0.99 and 0.99
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-0.99, 0.99);
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl AudioNode for Flanger {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let dry = self.input_in;
+
+        for side in 0..2 {
+            self.lfos[side].process();
+            let sweep = self.lfos[side].get_output_audio(OutputType::Out).unwrap() * self.depth * MAX_SWEEP;
+
+            // the newest sample gets written after reading, so it's one sample further back
+            let delay = (MIN_DELAY + sweep) * SAMPLE_RATE as f32 - 1.0;
+
+            self.wet[side] = self.lines[side].read(delay);
+            self.lines[side].write(dry + self.wet[side] * self.feedback);
+        }
+
+        let dry_level = 1.0 - self.mix;
+
+        self.output_left = dry * dry_level + self.wet[0] * self.mix;
+        self.output_right = dry * dry_level + self.wet[1] * self.mix;
+        self.output_out = (self.output_left + self.output_right) * 0.5;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            OutputType::Left => Ok(self.output_left),
+            OutputType::Right => Ok(self.output_right),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
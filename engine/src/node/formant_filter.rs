@@ -0,0 +1,181 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::node::filter::{Filter, FilterType, FrequencyResponse};
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::lerp;
+
+const FORMANT_COUNT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Vowel {
+    A,
+    E,
+    I,
+    O,
+    U,
+}
+
+/// frequency (hertz), bandwidth (hertz) and level (decibels) of each formant
+type VowelFormants = [(f32, f32, f32); FORMANT_COUNT];
+
+// adult male voice, in the order of `Vowel`
+const VOWEL_FORMANTS: [VowelFormants; 5] = [
+    [(800.0, 80.0, 0.0), (1150.0, 90.0, -6.0), (2900.0, 120.0, -32.0)],
+    [(350.0, 60.0, 0.0), (2000.0, 100.0, -20.0), (2800.0, 120.0, -15.0)],
+    [(270.0, 60.0, 0.0), (2140.0, 90.0, -12.0), (2950.0, 100.0, -26.0)],
+    [(450.0, 70.0, 0.0), (800.0, 80.0, -11.0), (2830.0, 100.0, -22.0)],
+    [(325.0, 50.0, 0.0), (700.0, 60.0, -16.0), (2700.0, 170.0, -35.0)],
+];
+
+/// Parallel bandpass filters tuned to the formants of a vowel, morphing between A, E, I, O and U
+///
+/// # Inputs
+/// `in` - Audio in, something bright like a sawtooth works best.
+/// `morph` - Added to the morph position (0 is A, 1 is U).
+///
+/// # Outputs
+/// `out` - Filtered audio.
+pub struct FormantFilter {
+    morph: f32, // between 0 and 1
+    formant_filters: [Filter; FORMANT_COUNT],
+    formant_levels: [f32; FORMANT_COUNT], // linear gain
+    prev_position: f32,
+    morph_in: f32,
+    input_in: f32,
+    output_out: f32,
+}
+
+impl FormantFilter {
+    pub fn new(vowel: Vowel) -> FormantFilter {
+        let mut new_filter = FormantFilter {
+            morph: 0.0,
+            formant_filters: [
+                Filter::new(FilterType::BandpassConstantPeak, 1_000.0, 1.0),
+                Filter::new(FilterType::BandpassConstantPeak, 1_000.0, 1.0),
+                Filter::new(FilterType::BandpassConstantPeak, 1_000.0, 1.0),
+            ],
+            formant_levels: [0.0; FORMANT_COUNT],
+            prev_position: 0.0,
+            morph_in: 0.0,
+            input_in: 0_f32,
+            output_out: 0_f32,
+        };
+
+        new_filter.set_vowel(vowel);
+        new_filter.retune(new_filter.morph);
+
+        new_filter
+    }
+
+    /// frequency, q and linear level of each formant at `position` (between 0 and 1) along the
+    /// vowels
+    fn formants(position: f32) -> [(f32, f32, f32); FORMANT_COUNT] {
+        let scaled = position * (VOWEL_FORMANTS.len() - 1) as f32;
+        let lower = (scaled as usize).min(VOWEL_FORMANTS.len() - 2);
+        let amount = scaled - lower as f32;
+
+        let mut formants = [(0.0, 0.0, 0.0); FORMANT_COUNT];
+
+        for (i, formant) in formants.iter_mut().enumerate() {
+            let (lower_freq, lower_bandwidth, lower_level) = VOWEL_FORMANTS[lower][i];
+            let (upper_freq, upper_bandwidth, upper_level) = VOWEL_FORMANTS[lower + 1][i];
+
+            let frequency = lerp(lower_freq, upper_freq, amount);
+            let bandwidth = lerp(lower_bandwidth, upper_bandwidth, amount);
+            let level = lerp(lower_level, upper_level, amount);
+
+            *formant = (frequency, frequency / bandwidth, f32::powf(10.0, level / 20.0));
+        }
+
+        formants
+    }
+
+    /// sets the formant filters to `position` (between 0 and 1) along the vowels
+    fn retune(&mut self, position: f32) {
+        for (i, (frequency, q, level)) in FormantFilter::formants(position).into_iter().enumerate() {
+            self.formant_filters[i].set_frequency(frequency);
+            self.formant_filters[i].set_q(q);
+            self.formant_levels[i] = level;
+        }
+
+        self.prev_position = position;
+    }
+
+    fn position(&self) -> f32 {
+        (self.morph + self.morph_in).clamp(0.0, 1.0)
+    }
+
+    pub fn set_vowel(&mut self, vowel: Vowel) {
+        let index = match vowel {
+            Vowel::A => 0,
+            Vowel::E => 1,
+            Vowel::I => 2,
+            Vowel::O => 3,
+            Vowel::U => 4,
+        };
+
+        self.morph = index as f32 / (VOWEL_FORMANTS.len() - 1) as f32;
+    }
+
+    pub fn get_morph(&self) -> f32 {
+        self.morph
+    }
+    /// between 0 (A) and 1 (U), going through E, I and O
+    pub fn set_morph(&mut self, morph: f32) {
+        self.morph = morph.clamp(0.0, 1.0);
+    }
+}
+
+impl AudioNode for FormantFilter {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::Morph => self.morph_in = input,
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let position = self.position();
+
+        if f32::abs(position - self.prev_position) > f32::EPSILON {
+            self.retune(position);
+        }
+
+        let mut output = 0.0;
+
+        for (filter, level) in self.formant_filters.iter_mut().zip(self.formant_levels.iter()) {
+            filter.receive_audio(InputType::In, self.input_in).unwrap();
+            filter.process();
+
+            output += filter.get_output_audio(OutputType::Out).unwrap() * level;
+        }
+
+        self.output_out = output;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
+
+impl FrequencyResponse for FormantFilter {
+    /// sum of the formant filters, tuned to the current morph even if no audio has run since
+    fn complex_response(&self, frequency: f32) -> (f32, f32) {
+        FormantFilter::formants(self.position()).into_iter().fold(
+            (0.0, 0.0),
+            |(real_sum, imaginary_sum), (formant_frequency, q, level)| {
+                let filter = Filter::new(FilterType::BandpassConstantPeak, formant_frequency, q);
+                let (real, imaginary) = filter.complex_response(frequency);
+
+                (real_sum + real * level, imaginary_sum + imaginary * level)
+            },
+        )
+    }
+}
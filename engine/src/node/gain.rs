@@ -3,8 +3,17 @@ use simple_error::SimpleError;
 
 use crate::node::{AudioNode, InputType, OutputType};
 
+/// Scales audio by a fixed gain
+///
+/// # Inputs
+/// `in` - Audio in.
+/// `envelope` - Extra gain multiplier for modulation (such as ducking), 1 if not connected.
+///
+/// # Outputs
+/// `out` - Audio out.
 pub struct Gain {
     input_in: f32,
+    envelope_in: f32,
     output_out: f32,
     gain: f32,
 }
@@ -13,6 +22,7 @@ impl Gain {
     pub fn new() -> Gain {
         Gain {
             input_in: 0_f32,
+            envelope_in: 1.0,
             output_out: 0_f32,
             gain: 0.4,
         }
@@ -31,6 +41,7 @@ impl AudioNode for Gain {
     fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
         match input_type {
             InputType::In => self.input_in = input,
+            InputType::Envelope => self.envelope_in = input,
             _ => bail!("Cannot receive {:?}", input_type),
         }
 
@@ -40,7 +51,7 @@ impl AudioNode for Gain {
     fn process(&mut self) {
         let input = self.input_in;
 
-        self.output_out = input * self.gain;
+        self.output_out = input * self.gain * self.envelope_in;
     }
 
     fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
@@ -0,0 +1,196 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::{PI, SAMPLE_RATE};
+use crate::node::filter::FrequencyResponse;
+use crate::node::{AudioNode, InputType, OutputType};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LadderSlope {
+    /// 12 dB per octave
+    TwoPole,
+    /// 24 dB per octave
+    FourPole,
+}
+
+/// A Moog-style 4 pole transistor ladder lowpass
+///
+/// Each stage saturates (tanh), so driving it harder or turning up the resonance gets gritty,
+/// and resonance from 1 up self-oscillates. The stages are zero delay feedback one poles, so the
+/// resonance behaves the same at any cutoff.
+///
+/// # Inputs
+/// `in` - Audio in.
+/// `filter_offset` - Cutoff offset in octaves.
+///
+/// # Outputs
+/// `out` - Filtered audio.
+pub struct LadderFilter {
+    frequency: f32,
+    resonance: f32, // between 0 and 1
+    drive: f32,
+    slope: LadderSlope,
+    dirty: bool,
+    g: f32,
+    stages: [f32; 4],
+    prev_offset: f32,
+    filter_offset_in: f32,
+    input_in: f32,
+    output_out: f32,
+}
+
+impl LadderFilter {
+    pub fn new(frequency: f32, resonance: f32) -> LadderFilter {
+        let mut new_filter = LadderFilter {
+            frequency,
+            resonance,
+            drive: 1.0,
+            slope: LadderSlope::FourPole,
+            dirty: true,
+            g: 0.0,
+            stages: [0.0; 4],
+            prev_offset: 0.0,
+            filter_offset_in: 0.0,
+            input_in: 0_f32,
+            output_out: 0_f32,
+        };
+
+        new_filter.recompute();
+
+        new_filter
+    }
+
+    /// cutoff with the offset applied
+    fn cutoff(&self) -> f32 {
+        (self.frequency * f32::powf(2.0, self.filter_offset_in)).clamp(0.01, SAMPLE_RATE as f32 * 0.45)
+    }
+
+    fn recompute(&mut self) {
+        let g = (PI * self.cutoff() / SAMPLE_RATE as f32).tan();
+
+        // gain of each one pole stage, after solving its own feedback
+        self.g = g / (1.0 + g);
+
+        self.dirty = false;
+    }
+
+    pub fn get_frequency(&self) -> f32 {
+        self.frequency
+    }
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.dirty = true;
+        self.frequency = frequency;
+    }
+
+    pub fn get_resonance(&self) -> f32 {
+        self.resonance
+    }
+    /// between 0 and 1, self-oscillates near 1
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance;
+    }
+
+    pub fn get_drive(&self) -> f32 {
+        self.drive
+    }
+    /// gain before the first stage, 1 is clean-ish
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive;
+    }
+
+    pub fn get_slope(&self) -> LadderSlope {
+        self.slope
+    }
+    pub fn set_slope(&mut self, slope: LadderSlope) {
+        self.slope = slope;
+    }
+}
+
+impl AudioNode for LadderFilter {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::FilterOffset => self.filter_offset_in = input,
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        if f32::abs(self.filter_offset_in - self.prev_offset) > f32::EPSILON || self.dirty {
+            // avoid excess recomputation
+            self.recompute();
+        }
+
+        let g = self.g;
+        let k = 4.0 * self.resonance;
+        let input = self.drive * self.input_in;
+
+        // estimate the last stage's output linearly, so the feedback doesn't need a sample of delay
+        // (y4 = g^4 * x + what the stage states contribute, with x = input - k * y4)
+        let state_contribution = (1.0 - g)
+            * (g * g * g * self.stages[0] + g * g * self.stages[1] + g * self.stages[2] + self.stages[3]);
+        let last_stage = (g * g * g * g * input + state_contribution) / (1.0 + k * g * g * g * g);
+
+        // feedback always comes from the last stage, the slope only picks where to listen
+        // (not saturated here, the first stage saturates its own input like the others)
+        let mut stage_input = input - k * last_stage;
+        let mut outputs = [0_f32; 4];
+
+        for (stage, output) in self.stages.iter_mut().zip(outputs.iter_mut()) {
+            let v = (f32::tanh(stage_input) - *stage) * g;
+            *output = v + *stage;
+            *stage = *output + v;
+
+            stage_input = *output;
+        }
+
+        self.output_out = match self.slope {
+            LadderSlope::TwoPole => outputs[1],
+            LadderSlope::FourPole => outputs[3],
+        };
+
+        self.prev_offset = self.filter_offset_in;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
+
+impl FrequencyResponse for LadderFilter {
+    /// the small signal response, with the stages' saturation left out
+    fn complex_response(&self, frequency: f32) -> (f32, f32) {
+        // each stage is an analog one pole, 1 / (1 + s), after the bilinear transform
+        let frequency = frequency.clamp(0.0, SAMPLE_RATE as f32 * 0.4999);
+        let omega = (PI * frequency / SAMPLE_RATE as f32).tan() / (PI * self.cutoff() / SAMPLE_RATE as f32).tan();
+
+        let stage_squared = complex_divide((1.0, 0.0), (1.0 - omega * omega, 2.0 * omega));
+        let stage_fourth = complex_multiply(stage_squared, stage_squared);
+
+        // G^n / (1 + k * G^4), as the feedback always comes from the fourth stage
+        let k = 4.0 * self.resonance;
+        let numerator = match self.slope {
+            LadderSlope::TwoPole => stage_squared,
+            LadderSlope::FourPole => stage_fourth,
+        };
+        let (real, imaginary) =
+            complex_divide(numerator, (1.0 + k * stage_fourth.0, k * stage_fourth.1));
+
+        (real * self.drive, imaginary * self.drive)
+    }
+}
+
+fn complex_multiply((a, b): (f32, f32), (c, d): (f32, f32)) -> (f32, f32) {
+    (a * c - b * d, a * d + b * c)
+}
+
+fn complex_divide((a, b): (f32, f32), (c, d): (f32, f32)) -> (f32, f32) {
+    let denominator_squared = c * c + d * d;
+
+    ((a * c + b * d) / denominator_squared, (b * c - a * d) / denominator_squared)
+}
@@ -0,0 +1,250 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::{PI, SAMPLE_RATE, TWO_PI};
+
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::lerp;
+use crate::util::random::Random;
+use crate::util::tempo::NoteLength;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Sawtooth,
+    RampDown,
+    Square,
+    /// a new random value every cycle, held for the whole cycle
+    SampleAndHold,
+    /// glides between random values, one per cycle
+    SmoothRandom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LfoPolarity {
+    /// between -1 and 1
+    Bipolar,
+    /// between 0 and 1
+    Unipolar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LfoRate {
+    Hertz(f32),
+    /// one cycle per note length, at the tempo set with `set_tempo`
+    Synced(NoteLength),
+}
+
+/// A low frequency oscillator for modulation
+///
+/// Shapes are computed directly instead of from the band limited wavetables, as they are far
+/// below the point where aliasing matters.
+///
+/// # Inputs
+/// `gate` - Restarts the delay and fade in on a rising edge, and the phase too if retriggering.
+///
+/// # Outputs
+/// `out` - Modulation signal.
+pub struct Lfo {
+    shape: LfoShape,
+    polarity: LfoPolarity,
+    rate: LfoRate,
+    tempo: f32, // in beats per minute
+    phase: f32, // in radians
+    phase_offset: f32, // in radians
+    delay: f32, // seconds before the lfo starts fading in
+    fade_in: f32, // seconds to reach full depth
+    since_trigger: f32, // seconds
+    retrigger: bool,
+    random: Random,
+    random_from: f32,
+    random_to: f32,
+    input_gate: f32,
+    prev_gate: f32,
+    output_out: f32,
+}
+
+impl Lfo {
+    pub fn new(shape: LfoShape, frequency: f32) -> Lfo {
+        let mut random = Random::default();
+        let random_to = random.next_bipolar();
+
+        Lfo {
+            shape,
+            polarity: LfoPolarity::Bipolar,
+            rate: LfoRate::Hertz(frequency),
+            tempo: 120.0,
+            phase: 0.0,
+            phase_offset: 0.0,
+            delay: 0.0,
+            fade_in: 0.0,
+            since_trigger: 0.0,
+            retrigger: false,
+            random,
+            random_from: 0.0,
+            random_to,
+            input_gate: 0_f32,
+            prev_gate: 0_f32,
+            output_out: 0_f32,
+        }
+    }
+
+    /// current rate in hertz, taking tempo sync into account
+    pub fn get_frequency(&self) -> f32 {
+        match self.rate {
+            LfoRate::Hertz(frequency) => frequency,
+            LfoRate::Synced(note_length) => note_length.frequency(self.tempo),
+        }
+    }
+
+    pub fn get_rate(&self) -> LfoRate {
+        self.rate
+    }
+    pub fn set_rate(&mut self, rate: LfoRate) {
+        self.rate = rate;
+    }
+
+    pub fn get_tempo(&self) -> f32 {
+        self.tempo
+    }
+    pub fn set_tempo(&mut self, tempo: f32) {
+        self.tempo = tempo;
+    }
+
+    pub fn get_shape(&self) -> LfoShape {
+        self.shape
+    }
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    pub fn get_polarity(&self) -> LfoPolarity {
+        self.polarity
+    }
+    pub fn set_polarity(&mut self, polarity: LfoPolarity) {
+        self.polarity = polarity;
+    }
+
+    pub fn get_phase_offset(&self) -> f32 {
+        self.phase_offset
+    }
+    /// in radians, moving every shape along the cycle (the random shapes pick their new values
+    /// at the offset point too)
+    pub fn set_phase_offset(&mut self, phase_offset: f32) {
+        self.phase_offset = phase_offset.rem_euclid(TWO_PI);
+    }
+
+    pub fn get_delay(&self) -> f32 {
+        self.delay
+    }
+    pub fn set_delay(&mut self, delay: f32) {
+        self.delay = delay;
+    }
+
+    pub fn get_fade_in(&self) -> f32 {
+        self.fade_in
+    }
+    pub fn set_fade_in(&mut self, fade_in: f32) {
+        self.fade_in = fade_in;
+    }
+
+    pub fn get_retrigger(&self) -> bool {
+        self.retrigger
+    }
+    pub fn set_retrigger(&mut self, retrigger: bool) {
+        self.retrigger = retrigger;
+    }
+
+    fn next_random(&mut self) {
+        self.random_from = self.random_to;
+        self.random_to = self.random.next_bipolar();
+    }
+
+    fn shape_value(&self) -> f32 {
+        // position in the cycle, between 0 and 1
+        let position = ((self.phase + self.phase_offset) / TWO_PI).rem_euclid(1.0);
+
+        match self.shape {
+            LfoShape::Sine => f32::sin(position * TWO_PI),
+            LfoShape::Triangle => 1.0 - 4.0 * ((position + 0.25).rem_euclid(1.0) - 0.5).abs(),
+            LfoShape::Sawtooth => position * 2.0 - 1.0,
+            LfoShape::RampDown => 1.0 - position * 2.0,
+            LfoShape::Square => {
+                if position < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::SampleAndHold => self.random_to,
+            LfoShape::SmoothRandom => {
+                // cosine interpolation, so it doesn't have corners at each new value
+                let amount = (1.0 - f32::cos(position * PI)) * 0.5;
+
+                lerp(self.random_from, self.random_to, amount)
+            }
+        }
+    }
+
+    fn depth(&self) -> f32 {
+        if self.since_trigger < self.delay {
+            0.0
+        } else if self.fade_in <= 0.0 {
+            1.0
+        } else {
+            ((self.since_trigger - self.delay) / self.fade_in).min(1.0)
+        }
+    }
+}
+
+impl AudioNode for Lfo {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::Gate => self.input_gate = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        if self.input_gate > 0.0 && self.prev_gate <= 0.0 {
+            self.since_trigger = 0.0;
+
+            if self.retrigger {
+                self.phase = 0.0;
+                self.next_random();
+            }
+        }
+        self.prev_gate = self.input_gate;
+
+        let phase_advance = self.get_frequency() / (SAMPLE_RATE as f32) * TWO_PI;
+        let new_phase = self.phase + phase_advance;
+
+        if (self.phase + self.phase_offset).rem_euclid(TWO_PI) + phase_advance >= TWO_PI {
+            // new cycle (counting from the offset), new random value
+            self.next_random();
+        }
+
+        self.phase = new_phase.rem_euclid(TWO_PI);
+
+        if self.since_trigger < self.delay + self.fade_in {
+            self.since_trigger += 1.0 / SAMPLE_RATE as f32;
+        }
+
+        let value = match self.polarity {
+            LfoPolarity::Bipolar => self.shape_value(),
+            LfoPolarity::Unipolar => (self.shape_value() + 1.0) * 0.5,
+        };
+
+        self.output_out = value * self.depth();
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
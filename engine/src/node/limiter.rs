@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::SAMPLE_RATE;
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::delay_line::DelayLine;
+use crate::util::{from_decibels, smoothing_coefficient, to_decibels};
+
+/// longest lookahead, in seconds
+pub const MAX_LOOKAHEAD: f32 = 0.02;
+
+/// A brickwall limiter, making sure nothing gets past the ceiling
+///
+/// The audio is delayed by the lookahead time, so the gain can start coming down before a peak
+/// arrives instead of clipping it. The gain needed for each sample is held for the lookahead
+/// time, then averaged over it, which ramps down smoothly and still reaches the right gain by
+/// the time the peak comes out.
+///
+/// # Inputs
+/// `in` - Audio in.
+///
+/// # Outputs
+/// `out` - Audio out, delayed by the lookahead time.
+/// `gain_reduction` - How far the audio is being turned down, in decibels (0 or more).
+pub struct Limiter {
+    ceiling: f32,     // in decibels
+    lookahead: usize, // in samples
+    release: f32,     // in seconds
+    release_coefficient: f32,
+    line: DelayLine,
+    sample_index: usize, // wraps around, so only ever compared by difference
+    held: VecDeque<(usize, f32)>, // rising gains with their sample index, lowest first
+    released_gain: f32,
+    window: VecDeque<f32>, // released gains over the lookahead, for averaging
+    window_sum: f64,       // f64 so adding and removing doesn't drift
+    gain: f32,
+    input_in: f32,
+    output_out: f32,
+}
+
+impl Limiter {
+    pub fn new(ceiling: f32) -> Limiter {
+        let mut new_limiter = Limiter {
+            ceiling: ceiling.min(0.0),
+            lookahead: 0,
+            release: 0.05,
+            release_coefficient: smoothing_coefficient(0.05),
+            line: DelayLine::new(MAX_LOOKAHEAD),
+            sample_index: 0,
+            held: VecDeque::new(),
+            released_gain: 1.0,
+            window: VecDeque::new(),
+            window_sum: 0.0,
+            gain: 1.0,
+            input_in: 0_f32,
+            output_out: 0_f32,
+        };
+
+        new_limiter.set_lookahead(0.005);
+
+        new_limiter
+    }
+
+    pub fn get_ceiling(&self) -> f32 {
+        self.ceiling
+    }
+    /// highest level that comes out, in decibels (0 or less)
+    pub fn set_ceiling(&mut self, ceiling: f32) {
+        self.ceiling = ceiling.min(0.0);
+    }
+
+    /// in seconds
+    pub fn get_lookahead(&self) -> f32 {
+        self.lookahead as f32 / SAMPLE_RATE as f32
+    }
+    /// in seconds, up to `MAX_LOOKAHEAD`, which is also how much latency it adds
+    pub fn set_lookahead(&mut self, lookahead: f32) {
+        self.lookahead = (lookahead.clamp(0.0, MAX_LOOKAHEAD) * SAMPLE_RATE as f32) as usize;
+
+        self.window = VecDeque::from(vec![self.released_gain; self.lookahead + 1]);
+        self.window_sum = self.released_gain as f64 * (self.lookahead + 1) as f64;
+    }
+
+    pub fn get_release(&self) -> f32 {
+        self.release
+    }
+    /// seconds to let the gain back up after a peak
+    pub fn set_release(&mut self, release: f32) {
+        self.release = release;
+        self.release_coefficient = smoothing_coefficient(release);
+    }
+
+    /// the lowest gain needed by any sample still within the lookahead
+    fn hold_gain(&mut self, gain: f32) -> f32 {
+        while let Some(&(_, back)) = self.held.back() {
+            if back >= gain {
+                self.held.pop_back();
+            } else {
+                break;
+            }
+        }
+        self.held.push_back((self.sample_index, gain));
+
+        while let Some(&(index, _)) = self.held.front() {
+            if self.sample_index.wrapping_sub(index) > self.lookahead {
+                self.held.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        self.held.front().unwrap().1
+    }
+}
+
+impl AudioNode for Limiter {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let ceiling = from_decibels(self.ceiling);
+        let level = self.input_in.abs();
+
+        let needed = if level > ceiling { ceiling / level } else { 1.0 };
+        let held = self.hold_gain(needed);
+        self.sample_index = self.sample_index.wrapping_add(1);
+
+        // down straight away (the averaging smooths it), back up at the release time
+        self.released_gain = if held < self.released_gain {
+            held
+        } else {
+            held + self.release_coefficient * (self.released_gain - held)
+        };
+
+        self.window.push_back(self.released_gain);
+        self.window_sum += self.released_gain as f64;
+        self.window_sum -= self.window.pop_front().unwrap() as f64;
+        self.gain = (self.window_sum / self.window.len() as f64) as f32;
+
+        self.line.write(self.input_in);
+        let delayed = self.line.read(self.lookahead as f32);
+
+        // rounding in the average could leave the tiniest overshoot
+        self.output_out = (delayed * self.gain).clamp(-ceiling, ceiling);
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            OutputType::GainReduction => Ok(-to_decibels(self.gain)),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
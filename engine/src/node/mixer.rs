@@ -0,0 +1,172 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::PI;
+use crate::node::{AudioNode, InputType, OutputType};
+
+/// How loud each side is as a channel moves across the stereo field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PanLaw {
+    /// -6 db each side in the center, quieter in the middle than at the edges
+    Linear,
+    /// -3 db each side in the center, the same loudness all the way across
+    ConstantPower,
+    /// -4.5 db each side in the center, halfway between the other two
+    Compromise,
+}
+
+impl PanLaw {
+    /// left and right gains, for a pan between -1 (left) and 1 (right)
+    pub fn gains(&self, pan: f32) -> (f32, f32) {
+        let position = (pan.clamp(-1.0, 1.0) + 1.0) * 0.5;
+
+        let linear = (1.0 - position, position);
+        let constant_power = (f32::cos(position * PI / 2.0), f32::sin(position * PI / 2.0));
+
+        match self {
+            PanLaw::Linear => linear,
+            PanLaw::ConstantPower => constant_power,
+            PanLaw::Compromise => (
+                f32::sqrt(linear.0 * constant_power.0),
+                f32::sqrt(linear.1 * constant_power.1),
+            ),
+        }
+    }
+}
+
+/// Settings for one channel of a `Mixer`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixerChannel {
+    pub level: f32, // linear gain
+    pub pan: f32,   // between -1 (left) and 1 (right)
+    pub mute: bool,
+    pub solo: bool,
+    pub sends: Vec<f32>, // level into each aux bus, after the channel level
+}
+
+impl MixerChannel {
+    fn new(aux_buses: usize) -> MixerChannel {
+        MixerChannel {
+            level: 1.0,
+            pan: 0.0,
+            mute: false,
+            solo: false,
+            sends: vec![0.0; aux_buses],
+        }
+    }
+}
+
+/// Sums any number of channels into stereo, with sends to aux buses (for a shared reverb or
+/// delay)
+///
+/// When any channel is soloed, only soloed channels are heard. Muting wins over soloing.
+///
+/// # Inputs
+/// `channel(n)` - Audio in for channel `n`.
+///
+/// # Outputs
+/// `out` - All the channels at their levels, in mono (ignoring pan).
+/// `left` - Left side.
+/// `right` - Right side.
+/// `aux(n)` - Aux bus `n`, in mono.
+pub struct Mixer {
+    channels: Vec<MixerChannel>,
+    pan_law: PanLaw,
+    inputs_in: Vec<f32>,
+    output_out: f32,
+    output_left: f32,
+    output_right: f32,
+    outputs_aux: Vec<f32>,
+}
+
+impl Mixer {
+    pub fn new(channels: usize, aux_buses: usize) -> Mixer {
+        Mixer {
+            channels: (0..channels).map(|_| MixerChannel::new(aux_buses)).collect(),
+            pan_law: PanLaw::ConstantPower,
+            inputs_in: vec![0.0; channels],
+            output_out: 0_f32,
+            output_left: 0_f32,
+            output_right: 0_f32,
+            outputs_aux: vec![0.0; aux_buses],
+        }
+    }
+
+    pub fn get_channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn get_channel(&self, index: usize) -> Option<&MixerChannel> {
+        self.channels.get(index)
+    }
+
+    pub fn get_channel_mut(&mut self, index: usize) -> Option<&mut MixerChannel> {
+        self.channels.get_mut(index)
+    }
+
+    /// adds a channel on the end, returning its index
+    pub fn add_channel(&mut self) -> usize {
+        self.channels.push(MixerChannel::new(self.outputs_aux.len()));
+        self.inputs_in.push(0.0);
+
+        self.channels.len() - 1
+    }
+
+    pub fn get_aux_count(&self) -> usize {
+        self.outputs_aux.len()
+    }
+
+    pub fn get_pan_law(&self) -> PanLaw {
+        self.pan_law
+    }
+    pub fn set_pan_law(&mut self, pan_law: PanLaw) {
+        self.pan_law = pan_law;
+    }
+}
+
+impl AudioNode for Mixer {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::Channel(index) if index < self.inputs_in.len() => self.inputs_in[index] = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let any_solo = self.channels.iter().any(|channel| channel.solo);
+
+        self.output_out = 0.0;
+        self.output_left = 0.0;
+        self.output_right = 0.0;
+        self.outputs_aux.iter_mut().for_each(|aux| *aux = 0.0);
+
+        for (channel, input) in self.channels.iter().zip(self.inputs_in.iter()) {
+            if channel.mute || (any_solo && !channel.solo) {
+                continue;
+            }
+
+            let sample = input * channel.level;
+            let (left_gain, right_gain) = self.pan_law.gains(channel.pan);
+
+            self.output_out += sample;
+            self.output_left += sample * left_gain;
+            self.output_right += sample * right_gain;
+
+            for (aux, send) in self.outputs_aux.iter_mut().zip(channel.sends.iter()) {
+                *aux += sample * send;
+            }
+        }
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            OutputType::Left => Ok(self.output_left),
+            OutputType::Right => Ok(self.output_right),
+            OutputType::Aux(index) if index < self.outputs_aux.len() => Ok(self.outputs_aux[index]),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::SAMPLE_RATE;
+
+use crate::node::envelope::{EnvelopeCurve, EnvelopeGenerator, TriggerMode};
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::lerp;
+
+/// One segment of a `MultiStageEnvelope`: move to `level` over `time` seconds, shaped by `curve`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Breakpoint {
+    pub level: f32,
+    pub time: f32,
+    pub curve: EnvelopeCurve,
+}
+
+impl Breakpoint {
+    pub fn new(level: f32, time: f32, curve: EnvelopeCurve) -> Breakpoint {
+        Breakpoint { level, time, curve }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Idle,
+    Delaying,
+    Segment(usize),
+    Sustaining(usize),
+}
+
+/// An envelope made out of any number of breakpoints
+///
+/// While the gate is held, the envelope stops at the sustain point (if there is one), or keeps
+/// cycling between the loop points (if there are any), which turns it into a complex lfo. Once
+/// the gate is released it skips to the breakpoints after the sustain point (or loop end), which
+/// make up the release.
+///
+/// Like `Envelope`, a new note starts from the current level instead of jumping to 0, and any
+/// delay before the first breakpoint holds that level.
+///
+/// # Inputs
+/// `gate` - Starts the envelope on a rising edge, releases it on a falling edge.
+/// `trigger` - Starts the envelope over on a rising edge while the gate is held, if retriggering.
+/// `note` - Accepted so it can stand in for an `Envelope`, but ignored.
+/// `velocity` - Between 0 and 1, latched when the envelope starts.
+///
+/// # Outputs
+/// `out` - Envelope level.
+/// `finished` - 1 once released all the way (or before the first note), otherwise 0.
+pub struct MultiStageEnvelope {
+    breakpoints: Vec<Breakpoint>,
+    sustain_point: Option<usize>,
+    loop_points: Option<(usize, usize)>, // first and last breakpoint of the loop
+    delay: f32,                          // in seconds, before the first breakpoint
+    velocity_sensitivity: f32,           // how much velocity scales the output, between 0 and 1
+    peak_level: f32,                     // latched from the velocity when the envelope starts
+    trigger_mode: TriggerMode,
+    stage: Stage,
+    curve_position: f32, // between 0 and 1
+    amplitude_anchor: f32, // level the current segment started from
+    current_value: f32,
+    input_gate: f32,
+    prev_gate: f32,
+    input_trigger: f32,
+    prev_trigger: f32,
+    velocity_in: f32,
+    output_out: f32,
+}
+
+impl MultiStageEnvelope {
+    pub fn new(breakpoints: Vec<Breakpoint>, sustain_point: Option<usize>) -> MultiStageEnvelope {
+        MultiStageEnvelope {
+            breakpoints,
+            sustain_point,
+            loop_points: None,
+            delay: 0.0,
+            velocity_sensitivity: 0.0,
+            peak_level: 1.0,
+            trigger_mode: TriggerMode::Legato,
+            stage: Stage::Idle,
+            curve_position: 0.0,
+            amplitude_anchor: 0.0,
+            current_value: 0.0,
+            input_gate: 0_f32,
+            prev_gate: 0_f32,
+            input_trigger: 0_f32,
+            prev_trigger: 0_f32,
+            velocity_in: 1.0,
+            output_out: 0_f32,
+        }
+    }
+
+    /// the same shape as `Envelope`, all linear
+    pub fn adsr(attack: f32, decay: f32, sustain: f32, release: f32) -> MultiStageEnvelope {
+        MultiStageEnvelope::new(
+            vec![
+                Breakpoint::new(1.0, attack, EnvelopeCurve::Linear),
+                Breakpoint::new(sustain, decay, EnvelopeCurve::Linear),
+                Breakpoint::new(0.0, release, EnvelopeCurve::Linear),
+            ],
+            Some(1),
+        )
+    }
+
+    /// delay, attack, hold at the peak, decay, sustain and release, all linear like `adsr`
+    pub fn dahdsr(
+        delay: f32,
+        attack: f32,
+        hold: f32,
+        decay: f32,
+        sustain: f32,
+        release: f32,
+    ) -> MultiStageEnvelope {
+        let mut new_envelope = MultiStageEnvelope::new(
+            vec![
+                Breakpoint::new(1.0, attack, EnvelopeCurve::Linear),
+                Breakpoint::new(1.0, hold, EnvelopeCurve::Linear),
+                Breakpoint::new(sustain, decay, EnvelopeCurve::Linear),
+                Breakpoint::new(0.0, release, EnvelopeCurve::Linear),
+            ],
+            Some(2),
+        );
+        new_envelope.set_delay(delay);
+
+        new_envelope
+    }
+
+    pub fn get_breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn set_breakpoints(&mut self, breakpoints: Vec<Breakpoint>) {
+        self.breakpoints = breakpoints;
+        self.stage = Stage::Idle;
+    }
+
+    pub fn get_sustain_point(&self) -> Option<usize> {
+        self.sustain_point
+    }
+
+    /// index of the breakpoint to hold while the gate is on
+    pub fn set_sustain_point(&mut self, sustain_point: Option<usize>) {
+        self.sustain_point = sustain_point;
+    }
+
+    pub fn get_loop_points(&self) -> Option<(usize, usize)> {
+        self.loop_points
+    }
+
+    /// first and last breakpoint (inclusive) to repeat while the gate is on
+    pub fn set_loop_points(&mut self, loop_points: Option<(usize, usize)>) {
+        self.loop_points = loop_points;
+    }
+
+    pub fn get_delay(&self) -> f32 {
+        self.delay
+    }
+
+    /// seconds to wait at the current level before the first breakpoint
+    pub fn set_delay(&mut self, delay: f32) {
+        self.delay = delay.max(0.0);
+    }
+
+    pub fn get_velocity_sensitivity(&self) -> f32 {
+        self.velocity_sensitivity
+    }
+
+    /// 0 ignores velocity, 1 scales the whole envelope by the note's velocity
+    pub fn set_velocity_sensitivity(&mut self, velocity_sensitivity: f32) {
+        self.velocity_sensitivity = velocity_sensitivity;
+    }
+
+    pub fn get_trigger_mode(&self) -> TriggerMode {
+        self.trigger_mode
+    }
+
+    pub fn set_trigger_mode(&mut self, trigger_mode: TriggerMode) {
+        self.trigger_mode = trigger_mode;
+    }
+
+    /// whether the envelope has released all the way, and its voice is silent
+    pub fn is_finished(&self) -> bool {
+        self.stage == Stage::Idle
+    }
+
+    fn start(&mut self) {
+        // new note, new velocity, but carry on from the same output level
+        let output = self.current_value * self.peak_level;
+
+        self.peak_level = 1.0 - self.velocity_sensitivity + self.velocity_sensitivity * self.velocity_in;
+        self.current_value = if self.peak_level > 0.0 {
+            output / self.peak_level
+        } else {
+            0.0
+        };
+
+        if self.delay > 0.0 {
+            self.curve_position = 0.0;
+            self.stage = Stage::Delaying;
+        } else {
+            self.start_segment(0);
+        }
+    }
+
+    fn start_segment(&mut self, index: usize) {
+        self.amplitude_anchor = self.current_value;
+        self.curve_position = 0.0;
+
+        self.stage = if index < self.breakpoints.len() {
+            Stage::Segment(index)
+        } else {
+            Stage::Idle
+        };
+    }
+
+    /// where the release starts, if the gate being released should skip ahead
+    fn release_start(&self, index: usize) -> Option<usize> {
+        if let Some(sustain_point) = self.sustain_point {
+            if index <= sustain_point {
+                return Some(sustain_point + 1);
+            }
+        } else if let Some((_, loop_end)) = self.loop_points {
+            if index <= loop_end {
+                return Some(loop_end + 1);
+            }
+        }
+
+        None
+    }
+
+    fn finish_segment(&mut self, index: usize, engaged: bool) {
+        self.current_value = self.breakpoints[index].level;
+
+        match (self.loop_points, self.sustain_point) {
+            (Some((loop_start, loop_end)), _) if engaged && index == loop_end => {
+                self.start_segment(loop_start);
+            }
+            (_, Some(sustain_point)) if engaged && index == sustain_point => {
+                self.stage = Stage::Sustaining(index);
+            }
+            _ => self.start_segment(index + 1),
+        }
+    }
+}
+
+impl AudioNode for MultiStageEnvelope {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::Gate => self.input_gate = input,
+            InputType::Trigger => self.input_trigger = input,
+            InputType::Note => {}
+            InputType::Velocity => self.velocity_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let engaged = self.input_gate > 0.0;
+        let was_engaged = self.prev_gate > 0.0;
+        self.prev_gate = self.input_gate;
+
+        let triggered = self.input_trigger > 0.0 && self.prev_trigger <= 0.0;
+        self.prev_trigger = self.input_trigger;
+
+        let retriggered = triggered && self.trigger_mode == TriggerMode::Retrigger;
+
+        if engaged && (!was_engaged || retriggered) {
+            self.start();
+        } else if !engaged && was_engaged {
+            let index = match self.stage {
+                Stage::Segment(index) | Stage::Sustaining(index) => Some(index),
+                Stage::Delaying => Some(0),
+                Stage::Idle => None,
+            };
+
+            if let Some(release_start) = index.and_then(|index| self.release_start(index)) {
+                self.start_segment(release_start);
+            }
+        }
+
+        if self.stage == Stage::Delaying {
+            self.curve_position += (1.0 / SAMPLE_RATE as f32) / self.delay;
+
+            if self.curve_position >= 1.0 {
+                self.start_segment(0);
+            }
+        }
+
+        if let Stage::Segment(index) = self.stage {
+            let breakpoint = self.breakpoints[index];
+
+            if breakpoint.time > 0.0 {
+                self.curve_position += (1.0 / SAMPLE_RATE as f32) / breakpoint.time;
+            } else {
+                self.curve_position = 1.0;
+            }
+
+            self.current_value = lerp(
+                self.amplitude_anchor,
+                breakpoint.level,
+                breakpoint.curve.apply(self.curve_position),
+            );
+
+            if self.curve_position >= 1.0 {
+                self.finish_segment(index, engaged);
+            }
+        }
+
+        self.output_out = self.current_value * self.peak_level;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            OutputType::Finished => Ok(if self.is_finished() { 1.0 } else { 0.0 }),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
+
+impl EnvelopeGenerator for MultiStageEnvelope {
+    fn get_velocity_sensitivity(&self) -> f32 {
+        MultiStageEnvelope::get_velocity_sensitivity(self)
+    }
+    fn set_velocity_sensitivity(&mut self, velocity_sensitivity: f32) {
+        MultiStageEnvelope::set_velocity_sensitivity(self, velocity_sensitivity);
+    }
+
+    fn get_trigger_mode(&self) -> TriggerMode {
+        MultiStageEnvelope::get_trigger_mode(self)
+    }
+    fn set_trigger_mode(&mut self, trigger_mode: TriggerMode) {
+        MultiStageEnvelope::set_trigger_mode(self, trigger_mode);
+    }
+
+    fn is_finished(&self) -> bool {
+        MultiStageEnvelope::is_finished(self)
+    }
+}
@@ -4,7 +4,8 @@ use simple_error::SimpleError;
 use crate::constants::{SAMPLE_RATE, TWO_PI};
 
 use crate::node::{AudioNode, InputType, OutputType};
-use crate::wave::interpolate::interpolate;
+use crate::util::random::Random;
+use crate::wave::interpolate::{interpolate, Interpolation};
 use crate::wave::tables::{WAVETABLE_SIZE};
 use crate::wave::tables::{SAWTOOTH_VALUES, SINE_VALUES, SQUARE_VALUES, TRIANGLE_VALUES};
 
@@ -13,6 +14,8 @@ pub trait Oscillator {
     fn set_frequency(&mut self, frequency: f32);
 }
 
+const DECLICK_TIME: f32 = 0.002; // seconds
+
 pub enum Waveform {
     Sine,
     Triangle,
@@ -20,16 +23,37 @@ pub enum Waveform {
     Square,
 }
 
+/// What happens to the phase when the gate goes high
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhaseMode {
+    /// keep running, the gate is ignored
+    FreeRunning,
+    /// restart at the start phase, for consistent attacks
+    KeySync,
+    /// jump to a random phase
+    Random,
+}
+
 /// A sinsouid oscillator
 ///
 /// # Inputs
-/// None currently.
+/// `gate` - Resets the phase on a rising edge, depending on the `PhaseMode`.
 ///
 /// # Outputs
 /// `out` - Mono waveform out.
 pub struct OscillatorNode {
     phase: f32,
     frequency: f32,
+    interpolation: Interpolation,
+    phase_mode: PhaseMode,
+    start_phase: f32, // in radians
+    started: bool,    // whether any audio has been processed yet
+    random: Random,
+    // when the phase jumps, the difference from the old output is faded out to avoid a click
+    declick_offset: f32,
+    declick_position: f32, // between 0 and 1
+    input_gate: f32,
+    prev_gate: f32,
     output_out: f32,
     wavetable: &'static Vec<[f32; WAVETABLE_SIZE]>,
 }
@@ -39,6 +63,15 @@ impl OscillatorNode {
         OscillatorNode {
             phase: 0_f32,
             frequency: 440_f32,
+            interpolation: Interpolation::Linear,
+            phase_mode: PhaseMode::FreeRunning,
+            start_phase: 0_f32,
+            started: false,
+            random: Random::default(),
+            declick_offset: 0_f32,
+            declick_position: 1_f32,
+            input_gate: 0_f32,
+            prev_gate: 0_f32,
             output_out: 0_f32,
             wavetable: match waveform {
                 Waveform::Sine => &*SINE_VALUES,
@@ -64,6 +97,53 @@ impl OscillatorNode {
             Waveform::Triangle => &*TRIANGLE_VALUES,
         };
     }
+
+    pub fn get_interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    pub fn get_phase_mode(&self) -> PhaseMode {
+        self.phase_mode
+    }
+
+    pub fn set_phase_mode(&mut self, phase_mode: PhaseMode) {
+        self.phase_mode = phase_mode;
+    }
+
+    pub fn get_start_phase(&self) -> f32 {
+        self.start_phase
+    }
+
+    /// phase (in radians) that the oscillator starts from, and that `reset_phase` and key sync
+    /// restart from
+    pub fn set_start_phase(&mut self, start_phase: f32) {
+        self.start_phase = start_phase.rem_euclid(TWO_PI);
+    }
+
+    pub fn get_phase(&self) -> f32 {
+        self.phase
+    }
+
+    /// jump back to the start phase (declicked)
+    pub fn reset_phase(&mut self) {
+        self.jump_to_phase(self.start_phase);
+    }
+
+    fn jump_to_phase(&mut self, phase: f32) {
+        // whatever is still fading out from a previous jump carries over
+        let current_output = self.output_out;
+
+        self.phase = phase;
+
+        let new_output = interpolate(self.wavetable, self.frequency, self.phase, self.interpolation);
+
+        self.declick_offset = current_output - new_output;
+        self.declick_position = 0.0;
+    }
 }
 
 impl Oscillator for OscillatorNode {
@@ -78,14 +158,49 @@ impl Oscillator for OscillatorNode {
 
 impl AudioNode for OscillatorNode {
     fn process(&mut self) {
-        let phase_advance = self.frequency / (SAMPLE_RATE as f32) * TWO_PI;
-        self.phase = (self.phase + phase_advance) % TWO_PI;
+        if self.started {
+            let phase_advance = self.frequency / (SAMPLE_RATE as f32) * TWO_PI;
+            self.phase = (self.phase + phase_advance).rem_euclid(TWO_PI); // negative frequencies run backwards
+        } else {
+            // the very first sample is at the start phase, in any phase mode
+            self.phase = self.start_phase;
+            self.started = true;
+        }
+
+        let rising_edge = self.input_gate > 0.0 && self.prev_gate <= 0.0;
+        self.prev_gate = self.input_gate;
+
+        if rising_edge {
+            match self.phase_mode {
+                PhaseMode::FreeRunning => {}
+                PhaseMode::KeySync => self.reset_phase(),
+                PhaseMode::Random => {
+                    let phase = self.random.next_f32() * TWO_PI;
+                    self.jump_to_phase(phase);
+                }
+            }
+        }
 
-        self.output_out = interpolate(self.wavetable, self.frequency, self.phase);
+        self.output_out = interpolate(
+            self.wavetable,
+            self.frequency,
+            self.phase,
+            self.interpolation,
+        );
+
+        if self.declick_position < 1.0 {
+            self.declick_position += (1.0 / SAMPLE_RATE as f32) / DECLICK_TIME;
+            self.output_out += self.declick_offset * (1.0 - self.declick_position).max(0.0);
+        }
     }
 
-    fn receive_audio(&mut self, input_type: InputType, _input: f32) -> Result<(), SimpleError> {
-        bail!("Cannot receive {:?}", input_type);
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::Gate => self.input_gate = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
     }
 
     fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
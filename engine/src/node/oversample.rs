@@ -0,0 +1,243 @@
+use simple_error::SimpleError;
+
+use crate::constants::{PI, SAMPLE_RATE};
+use crate::node::{AudioNode, InputType, OutputType};
+
+// must be 4k + 3, so the center tap lands on an odd index and the polyphase split works out
+const HALF_BAND_TAPS: usize = 31;
+const HALF_BAND_CENTER: usize = HALF_BAND_TAPS / 2;
+
+lazy_static! {
+    // Blackman windowed sinc cutting off at a quarter of the (higher) sample rate. Every other
+    // tap apart from the center is zero, which the up and downsamplers skip.
+    static ref HALF_BAND_KERNEL: [f32; HALF_BAND_TAPS] = {
+        let mut kernel = [0_f32; HALF_BAND_TAPS];
+
+        for (i, tap) in kernel.iter_mut().enumerate() {
+            let n = i as f32 - HALF_BAND_CENTER as f32;
+
+            let sinc = if i == HALF_BAND_CENTER {
+                1.0
+            } else {
+                f32::sin(PI * n / 2.0) / (PI * n / 2.0)
+            };
+
+            let window_position = i as f32 / (HALF_BAND_TAPS - 1) as f32;
+            let window = 0.42 - 0.5 * f32::cos(2.0 * PI * window_position)
+                + 0.08 * f32::cos(4.0 * PI * window_position);
+
+            *tap = 0.5 * sinc * window;
+        }
+
+        let sum: f32 = kernel.iter().sum();
+        for tap in kernel.iter_mut() {
+            *tap /= sum;
+        }
+
+        kernel
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OversampleFactor {
+    X2,
+    X4,
+    X8,
+}
+
+impl OversampleFactor {
+    pub fn multiplier(&self) -> usize {
+        match self {
+            OversampleFactor::X2 => 2,
+            OversampleFactor::X4 => 4,
+            OversampleFactor::X8 => 8,
+        }
+    }
+
+    /// how many half band stages it takes to get there
+    fn stages(&self) -> usize {
+        match self {
+            OversampleFactor::X2 => 1,
+            OversampleFactor::X4 => 2,
+            OversampleFactor::X8 => 3,
+        }
+    }
+}
+
+/// doubles the sample rate, outputting two samples for each one in
+struct HalfBandUpsampler {
+    history: [f32; HALF_BAND_CENTER + 1], // newest first
+}
+
+impl HalfBandUpsampler {
+    fn new() -> HalfBandUpsampler {
+        HalfBandUpsampler {
+            history: [0.0; HALF_BAND_CENTER + 1],
+        }
+    }
+
+    fn process(&mut self, input: f32) -> [f32; 2] {
+        self.history.copy_within(0..HALF_BAND_CENTER, 1);
+        self.history[0] = input;
+
+        // zero stuffing halves the level, so both phases are doubled
+        let mut even = 0.0;
+        for (i, sample) in self.history.iter().enumerate() {
+            even += HALF_BAND_KERNEL[i * 2] * sample;
+        }
+
+        // the odd phase only has the center tap left
+        let odd = self.history[(HALF_BAND_CENTER - 1) / 2] * HALF_BAND_KERNEL[HALF_BAND_CENTER];
+
+        [even * 2.0, odd * 2.0]
+    }
+}
+
+/// halves the sample rate, taking two samples for each one out
+struct HalfBandDownsampler {
+    history: [f32; HALF_BAND_TAPS], // newest first
+}
+
+impl HalfBandDownsampler {
+    fn new() -> HalfBandDownsampler {
+        HalfBandDownsampler {
+            history: [0.0; HALF_BAND_TAPS],
+        }
+    }
+
+    fn process(&mut self, input: [f32; 2]) -> f32 {
+        self.history.copy_within(0..HALF_BAND_TAPS - 2, 2);
+        self.history[1] = input[0];
+        self.history[0] = input[1];
+
+        let mut output = self.history[HALF_BAND_CENTER] * HALF_BAND_KERNEL[HALF_BAND_CENTER];
+        for i in (0..HALF_BAND_TAPS).step_by(2) {
+            output += HALF_BAND_KERNEL[i] * self.history[i];
+        }
+
+        output
+    }
+}
+
+/// Runs a node (or a whole subgraph implementing `AudioNode`) at a multiple of the sample rate,
+/// so nonlinear processing doesn't alias as badly
+///
+/// Nodes in this engine assume they run at `SAMPLE_RATE`, so anything inside runs `multiplier`
+/// times faster than it thinks: configure frequencies with `to_inner_frequency` and times with
+/// `to_inner_time`.
+///
+/// # Inputs
+/// `in` - Audio in, upsampled before being passed on.
+/// Anything else is passed straight to the wrapped node and held for all of the oversampled steps.
+///
+/// # Outputs
+/// `out` - The wrapped node's `out`, downsampled.
+/// Anything else is read straight from the wrapped node (its most recent oversampled value).
+pub struct Oversample<T: AudioNode> {
+    node: T,
+    factor: OversampleFactor,
+    upsamplers: Vec<HalfBandUpsampler>,
+    downsamplers: Vec<HalfBandDownsampler>,
+    buffer: Vec<f32>,
+    next_buffer: Vec<f32>,
+    input_in: f32,
+    output_out: f32,
+}
+
+impl<T: AudioNode> Oversample<T> {
+    pub fn new(node: T, factor: OversampleFactor) -> Oversample<T> {
+        Oversample {
+            node,
+            factor,
+            upsamplers: (0..factor.stages()).map(|_| HalfBandUpsampler::new()).collect(),
+            downsamplers: (0..factor.stages()).map(|_| HalfBandDownsampler::new()).collect(),
+            buffer: Vec::with_capacity(factor.multiplier()),
+            next_buffer: Vec::with_capacity(factor.multiplier()),
+            input_in: 0_f32,
+            output_out: 0_f32,
+        }
+    }
+
+    pub fn get_factor(&self) -> OversampleFactor {
+        self.factor
+    }
+
+    /// the rate the wrapped node actually runs at
+    pub fn get_sample_rate(&self) -> u32 {
+        SAMPLE_RATE * self.factor.multiplier() as u32
+    }
+
+    /// what to set a wrapped node's frequency to, for it to come out at `frequency`
+    pub fn to_inner_frequency(&self, frequency: f32) -> f32 {
+        frequency / self.factor.multiplier() as f32
+    }
+
+    /// what to set a wrapped node's time to, for it to take `seconds`
+    pub fn to_inner_time(&self, seconds: f32) -> f32 {
+        seconds * self.factor.multiplier() as f32
+    }
+
+    pub fn get_node(&self) -> &T {
+        &self.node
+    }
+
+    pub fn get_node_mut(&mut self) -> &mut T {
+        &mut self.node
+    }
+
+    /// unwraps the node, to run it at the normal rate again
+    pub fn into_node(self) -> T {
+        self.node
+    }
+}
+
+impl<T: AudioNode> AudioNode for Oversample<T> {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => self.node.receive_audio(input_type, input)?,
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        self.buffer.clear();
+        self.buffer.push(self.input_in);
+
+        for upsampler in self.upsamplers.iter_mut() {
+            self.next_buffer.clear();
+
+            for sample in self.buffer.iter() {
+                self.next_buffer.extend_from_slice(&upsampler.process(*sample));
+            }
+
+            std::mem::swap(&mut self.buffer, &mut self.next_buffer);
+        }
+
+        for sample in self.buffer.iter_mut() {
+            self.node.receive_audio(InputType::In, *sample).unwrap();
+            self.node.process();
+            *sample = self.node.get_output_audio(OutputType::Out).unwrap();
+        }
+
+        for downsampler in self.downsamplers.iter_mut().rev() {
+            self.next_buffer.clear();
+
+            for pair in self.buffer.chunks_exact(2) {
+                self.next_buffer.push(downsampler.process([pair[0], pair[1]]));
+            }
+
+            std::mem::swap(&mut self.buffer, &mut self.next_buffer);
+        }
+
+        self.output_out = self.buffer[0];
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => self.node.get_output_audio(output_type),
+        }
+    }
+}
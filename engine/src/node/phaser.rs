@@ -0,0 +1,199 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::{PI, SAMPLE_RATE, TWO_PI};
+use crate::node::lfo::{Lfo, LfoRate, LfoShape};
+use crate::node::{AudioNode, InputType, OutputType};
+
+pub const MAX_STAGES: usize = 8;
+/// how many octaves either side of the center frequency the stages sweep at full depth
+const MAX_SWEEP: f32 = 3.0;
+const STAGE_Q: f32 = 0.5;
+
+/// A second order allpass, with its coefficients passed in so every stage on a side can share them
+#[derive(Default)]
+struct AllpassStage {
+    prev_input_1: f32,
+    prev_input_2: f32,
+    prev_output_1: f32,
+    prev_output_2: f32,
+}
+
+impl AllpassStage {
+    /// `a1` and `a2` are the (normalized) feedback coefficients, the feedforward ones mirror them
+    fn process(&mut self, input: f32, (a1, a2): (f32, f32)) -> f32 {
+        let output = a2 * input + a1 * self.prev_input_1 + self.prev_input_2
+            - a1 * self.prev_output_1
+            - a2 * self.prev_output_2;
+
+        self.prev_input_2 = self.prev_input_1;
+        self.prev_input_1 = input;
+        self.prev_output_2 = self.prev_output_1;
+        self.prev_output_1 = output;
+
+        output
+    }
+}
+
+/// the same allpass as `Filter` (from the RBJ audio EQ cookbook), worked out once per side
+fn allpass_coefficients(frequency: f32) -> (f32, f32) {
+    let frequency = frequency.clamp(0.01, SAMPLE_RATE as f32 * 0.49);
+
+    let w0 = TWO_PI * frequency / SAMPLE_RATE as f32;
+    let alpha = w0.sin() / (2.0 * STAGE_Q);
+    let a0 = 1.0 + alpha;
+
+    (-2.0 * w0.cos() / a0, (1.0 - alpha) / a0)
+}
+
+/// A phaser, sweeping notches through the sound with a chain of allpass filters
+///
+/// Each stage is a second order allpass, so every stage adds one notch when mixed with the dry
+/// signal (strongest at a mix of 0.5). The right side's lfo is a quarter cycle ahead of the
+/// left's.
+///
+/// # Inputs
+/// `in` - Audio in.
+///
+/// # Outputs
+/// `out` - Both sides mixed to mono.
+/// `left` - Left side.
+/// `right` - Right side.
+pub struct Phaser {
+    rate: f32,      // in hertz
+    depth: f32,     // between 0 and 1
+    frequency: f32, // center of the sweep
+    feedback: f32,
+    mix: f32, // 0 is completely dry, 1 is completely wet
+    stages: [Vec<AllpassStage>; 2],
+    lfos: [Lfo; 2],
+    wet: [f32; 2], // last output of each side, for feedback
+    input_in: f32,
+    output_out: f32,
+    output_left: f32,
+    output_right: f32,
+}
+
+impl Phaser {
+    pub fn new(rate: f32, depth: f32) -> Phaser {
+        let mut lfos = [Lfo::new(LfoShape::Sine, rate), Lfo::new(LfoShape::Sine, rate)];
+        lfos[1].set_phase_offset(PI / 2.0);
+
+        let mut new_phaser = Phaser {
+            rate,
+            depth: depth.clamp(0.0, 1.0),
+            frequency: 800.0,
+            feedback: 0.0,
+            mix: 0.5,
+            stages: [vec![], vec![]],
+            lfos,
+            wet: [0.0; 2],
+            input_in: 0_f32,
+            output_out: 0_f32,
+            output_left: 0_f32,
+            output_right: 0_f32,
+        };
+
+        new_phaser.set_stages(4);
+
+        new_phaser
+    }
+
+    pub fn get_stages(&self) -> usize {
+        self.stages[0].len()
+    }
+    /// number of allpass filters (and notches), up to `MAX_STAGES`
+    pub fn set_stages(&mut self, stages: usize) {
+        let stages = stages.clamp(1, MAX_STAGES);
+
+        for side in self.stages.iter_mut() {
+            *side = (0..stages).map(|_| AllpassStage::default()).collect();
+        }
+    }
+
+    pub fn get_rate(&self) -> f32 {
+        self.rate
+    }
+    /// in hertz
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate;
+
+        for lfo in self.lfos.iter_mut() {
+            lfo.set_rate(LfoRate::Hertz(rate));
+        }
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.depth
+    }
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn get_frequency(&self) -> f32 {
+        self.frequency
+    }
+    /// center of the sweep, in hertz
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency;
+    }
+
+    pub fn get_feedback(&self) -> f32 {
+        self.feedback
+    }
+    /// between -0.95 and 0.95, sharpening the notches
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-0.95, 0.95);
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl AudioNode for Phaser {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let dry = self.input_in;
+
+        for side in 0..2 {
+            self.lfos[side].process();
+            let offset = self.lfos[side].get_output_audio(OutputType::Out).unwrap() * self.depth * MAX_SWEEP;
+            let coefficients = allpass_coefficients(self.frequency * f32::powf(2.0, offset));
+
+            let mut sample = dry + self.wet[side] * self.feedback;
+
+            for stage in self.stages[side].iter_mut() {
+                sample = stage.process(sample, coefficients);
+            }
+
+            self.wet[side] = sample;
+        }
+
+        let dry_level = 1.0 - self.mix;
+
+        self.output_left = dry * dry_level + self.wet[0] * self.mix;
+        self.output_right = dry * dry_level + self.wet[1] * self.mix;
+        self.output_out = (self.output_left + self.output_right) * 0.5;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            OutputType::Left => Ok(self.output_left),
+            OutputType::Right => Ok(self.output_right),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::SAMPLE_RATE;
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::delay_line::DelayLine;
+
+/// longest pre-delay, in seconds
+pub const MAX_PRE_DELAY: f32 = 0.5;
+
+// Freeverb's tunings, in samples at 44.1khz
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+const TUNING_RATE: f32 = 44_100.0;
+
+// how far `size` can stretch the tunings either way
+const MIN_SIZE_SCALE: f32 = 0.5;
+const MAX_SIZE_SCALE: f32 = 1.5;
+
+const ALLPASS_FEEDBACK: f32 = 0.5;
+const INPUT_GAIN: f32 = 0.015; // the combs add up to a lot
+
+/// feedback comb filter with a one pole lowpass in the loop
+struct Comb {
+    buffer: Vec<f32>,
+    length: usize,
+    index: usize,
+    feedback: f32,
+    damping: f32,
+    filter_store: f32,
+}
+
+impl Comb {
+    fn new(capacity: usize) -> Comb {
+        Comb {
+            buffer: vec![0.0; capacity],
+            length: capacity,
+            index: 0,
+            feedback: 0.0,
+            damping: 0.0,
+            filter_store: 0.0,
+        }
+    }
+
+    fn set_length(&mut self, length: usize) {
+        self.length = length.clamp(1, self.buffer.len());
+        self.index %= self.length;
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.buffer[self.index];
+
+        self.filter_store = output * (1.0 - self.damping) + self.filter_store * self.damping;
+        self.buffer[self.index] = input + self.filter_store * self.feedback;
+
+        self.index = (self.index + 1) % self.length;
+
+        output
+    }
+}
+
+/// Schroeder allpass, smearing the echoes from the combs into a smooth tail
+struct Allpass {
+    buffer: Vec<f32>,
+    length: usize,
+    index: usize,
+}
+
+impl Allpass {
+    fn new(capacity: usize) -> Allpass {
+        Allpass {
+            buffer: vec![0.0; capacity],
+            length: capacity,
+            index: 0,
+        }
+    }
+
+    fn set_length(&mut self, length: usize) {
+        self.length = length.clamp(1, self.buffer.len());
+        self.index %= self.length;
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let buffered = self.buffer[self.index];
+
+        self.buffer[self.index] = input + buffered * ALLPASS_FEEDBACK;
+        self.index = (self.index + 1) % self.length;
+
+        buffered - input
+    }
+}
+
+/// One side of the reverb: parallel combs into series allpasses
+struct ReverbChannel {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+    spread: usize, // extra samples on every delay, to decorrelate the sides
+}
+
+impl ReverbChannel {
+    fn new(spread: usize) -> ReverbChannel {
+        ReverbChannel {
+            combs: COMB_TUNINGS
+                .iter()
+                .map(|tuning| Comb::new(scaled_length(tuning + spread, MAX_SIZE_SCALE)))
+                .collect(),
+            allpasses: ALLPASS_TUNINGS
+                .iter()
+                .map(|tuning| Allpass::new(scaled_length(tuning + spread, 1.0)))
+                .collect(),
+            spread,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let mut output: f32 = self.combs.iter_mut().map(|comb| comb.process(input)).sum();
+
+        for allpass in self.allpasses.iter_mut() {
+            output = allpass.process(output);
+        }
+
+        output
+    }
+}
+
+/// delay length in samples at this sample rate
+fn scaled_length(tuning: usize, scale: f32) -> usize {
+    (tuning as f32 * scale * SAMPLE_RATE as f32 / TUNING_RATE) as usize
+}
+
+/// A Freeverb style reverb
+///
+/// Eight lowpass feedback combs in parallel, followed by four allpasses in series, for each
+/// side. It only uses whole sample delays and no interpolation, so it's cheap enough to run
+/// on a Raspberry Pi.
+///
+/// # Inputs
+/// `in` - Audio in.
+///
+/// # Outputs
+/// `out` - Dry and wet audio mixed together, in mono.
+/// `left` - Left side.
+/// `right` - Right side.
+pub struct Reverb {
+    size: f32,       // between 0 and 1
+    decay: f32,      // seconds for the tail to fall by 60 db
+    damping: f32,    // between 0 and 1
+    pre_delay: f32,  // in seconds
+    width: f32,      // 0 is mono, 1 is fully stereo
+    mix: f32,        // 0 is completely dry, 1 is completely wet
+    channels: [ReverbChannel; 2],
+    pre_delay_line: DelayLine,
+    input_in: f32,
+    output_out: f32,
+    output_left: f32,
+    output_right: f32,
+}
+
+impl Reverb {
+    pub fn new(size: f32, decay: f32) -> Reverb {
+        let mut new_reverb = Reverb {
+            size: size.clamp(0.0, 1.0),
+            decay,
+            damping: 0.5,
+            pre_delay: 0.0,
+            width: 1.0,
+            mix: 0.3,
+            channels: [ReverbChannel::new(0), ReverbChannel::new(STEREO_SPREAD)],
+            pre_delay_line: DelayLine::new(MAX_PRE_DELAY),
+            input_in: 0_f32,
+            output_out: 0_f32,
+            output_left: 0_f32,
+            output_right: 0_f32,
+        };
+
+        new_reverb.recompute();
+
+        new_reverb
+    }
+
+    /// sets the delay lengths and comb feedback from the size, decay and damping
+    fn recompute(&mut self) {
+        let scale = MIN_SIZE_SCALE + (MAX_SIZE_SCALE - MIN_SIZE_SCALE) * self.size;
+
+        for channel in self.channels.iter_mut() {
+            for (comb, tuning) in channel.combs.iter_mut().zip(COMB_TUNINGS.iter()) {
+                let length = scaled_length(tuning + channel.spread, scale);
+                comb.set_length(length);
+
+                // each comb loses 60 db over `decay` seconds, however long it is
+                comb.feedback = if self.decay > 0.0 {
+                    f32::powf(10.0, -3.0 * length as f32 / (self.decay * SAMPLE_RATE as f32))
+                } else {
+                    0.0
+                };
+                comb.damping = self.damping;
+            }
+
+            for (allpass, tuning) in channel.allpasses.iter_mut().zip(ALLPASS_TUNINGS.iter()) {
+                allpass.set_length(scaled_length(tuning + channel.spread, 1.0));
+            }
+        }
+    }
+
+    pub fn get_size(&self) -> f32 {
+        self.size
+    }
+    /// how big the room is, between 0 and 1
+    pub fn set_size(&mut self, size: f32) {
+        self.size = size.clamp(0.0, 1.0);
+        self.recompute();
+    }
+
+    pub fn get_decay(&self) -> f32 {
+        self.decay
+    }
+    /// seconds for the tail to fall by 60 db
+    pub fn set_decay(&mut self, decay: f32) {
+        self.decay = decay.max(0.0);
+        self.recompute();
+    }
+
+    pub fn get_damping(&self) -> f32 {
+        self.damping
+    }
+    /// how much quicker high frequencies die away, between 0 and 1
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping.clamp(0.0, 1.0);
+        self.recompute();
+    }
+
+    pub fn get_pre_delay(&self) -> f32 {
+        self.pre_delay
+    }
+    /// seconds before the reverb starts, up to `MAX_PRE_DELAY`
+    pub fn set_pre_delay(&mut self, pre_delay: f32) {
+        self.pre_delay = pre_delay.clamp(0.0, MAX_PRE_DELAY);
+    }
+
+    pub fn get_width(&self) -> f32 {
+        self.width
+    }
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 1.0);
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl AudioNode for Reverb {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let dry = self.input_in;
+
+        self.pre_delay_line.write(dry);
+        let delayed = self.pre_delay_line.read(self.pre_delay * SAMPLE_RATE as f32) * INPUT_GAIN;
+
+        let left = self.channels[0].process(delayed);
+        let right = self.channels[1].process(delayed);
+
+        // crossfeed the sides to narrow the image
+        let same_side = (1.0 + self.width) * 0.5;
+        let other_side = (1.0 - self.width) * 0.5;
+
+        let wet_left = left * same_side + right * other_side;
+        let wet_right = right * same_side + left * other_side;
+
+        let dry_level = 1.0 - self.mix;
+
+        self.output_left = dry * dry_level + wet_left * self.mix;
+        self.output_right = dry * dry_level + wet_right * self.mix;
+        self.output_out = dry * dry_level + (wet_left + wet_right) * 0.5 * self.mix;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            OutputType::Left => Ok(self.output_left),
+            OutputType::Right => Ok(self.output_right),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::node::oscillator::{Oscillator, OscillatorNode, Waveform};
+use crate::node::{AudioNode, InputType, OutputType};
+use crate::util::lerp;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CarrierSource {
+    /// the built in sine oscillator, at `set_frequency`
+    Internal,
+    /// whatever arrives at the `carrier` input
+    External,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModulationMode {
+    /// multiplies by the carrier as it is, leaving only the sum and difference frequencies
+    Ring,
+    /// lifts a -1 to 1 carrier up to between 0 and 1 first, keeping the original frequencies too
+    Amplitude,
+}
+
+/// Multiplies two signals together at audio rate
+///
+/// # Inputs
+/// `modulator` - Audio to modulate (`in` works too).
+/// `carrier` - Signal to multiply by, when using an external carrier.
+///
+/// # Outputs
+/// `out` - Audio out.
+pub struct RingModulator {
+    source: CarrierSource,
+    mode: ModulationMode,
+    depth: f32, // 0 leaves the modulator alone, 1 is fully modulated
+    mix: f32,   // 0 is completely dry, 1 is completely wet
+    oscillator: OscillatorNode,
+    carrier_in: f32,
+    modulator_in: f32,
+    output_out: f32,
+}
+
+impl RingModulator {
+    pub fn new(source: CarrierSource) -> RingModulator {
+        RingModulator {
+            source,
+            mode: ModulationMode::Ring,
+            depth: 1.0,
+            mix: 1.0,
+            oscillator: OscillatorNode::new_with_frequency(Waveform::Sine, 440.0),
+            carrier_in: 0_f32,
+            modulator_in: 0_f32,
+            output_out: 0_f32,
+        }
+    }
+
+    pub fn get_source(&self) -> CarrierSource {
+        self.source
+    }
+    pub fn set_source(&mut self, source: CarrierSource) {
+        self.source = source;
+    }
+
+    pub fn get_mode(&self) -> ModulationMode {
+        self.mode
+    }
+    pub fn set_mode(&mut self, mode: ModulationMode) {
+        self.mode = mode;
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.depth
+    }
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    pub fn get_frequency(&self) -> f32 {
+        self.oscillator.get_frequency()
+    }
+    /// frequency of the internal carrier
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.oscillator.set_frequency(frequency);
+    }
+
+    pub fn set_carrier_waveform(&mut self, waveform: Waveform) {
+        self.oscillator.set_waveform(waveform);
+    }
+}
+
+impl AudioNode for RingModulator {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::Modulator | InputType::In => self.modulator_in = input,
+            InputType::Carrier => self.carrier_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let carrier = match self.source {
+            CarrierSource::Internal => {
+                self.oscillator.process();
+                self.oscillator.get_output_audio(OutputType::Out).unwrap()
+            }
+            CarrierSource::External => self.carrier_in,
+        };
+
+        let carrier = match self.mode {
+            ModulationMode::Ring => carrier,
+            ModulationMode::Amplitude => (carrier + 1.0) * 0.5,
+        };
+
+        let dry = self.modulator_in;
+        let wet = dry * lerp(1.0, carrier, self.depth);
+
+        self.output_out = lerp(dry, wet, self.mix);
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
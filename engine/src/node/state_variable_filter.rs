@@ -0,0 +1,209 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::constants::{PI, SAMPLE_RATE};
+use crate::node::filter::FrequencyResponse;
+use crate::node::{AudioNode, InputType, OutputType};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SvfOutput {
+    Lowpass,
+    Bandpass,
+    Highpass,
+    Notch,
+}
+
+/// The response of one of a `StateVariableFilter`'s outputs, from `StateVariableFilter::response`
+pub struct SvfResponse {
+    g: f32,
+    k: f32,
+    output: SvfOutput,
+}
+
+impl FrequencyResponse for SvfResponse {
+    fn complex_response(&self, frequency: f32) -> (f32, f32) {
+        // the bilinear transform maps the unit circle onto s = j * tan(w / 2), scaled by the cutoff
+        let frequency = frequency.clamp(0.0, SAMPLE_RATE as f32 * 0.4999);
+        let omega = (PI * frequency / SAMPLE_RATE as f32).tan() / self.g;
+
+        // the analog prototype, with s = j * omega
+        let denominator_real = 1.0 - omega * omega;
+        let denominator_imaginary = self.k * omega;
+
+        let (numerator_real, numerator_imaginary) = match self.output {
+            SvfOutput::Lowpass => (1.0, 0.0),
+            SvfOutput::Bandpass => (0.0, self.k * omega),
+            SvfOutput::Highpass => (-omega * omega, 0.0),
+            SvfOutput::Notch => (1.0 - omega * omega, 0.0),
+        };
+
+        let denominator_squared =
+            denominator_real * denominator_real + denominator_imaginary * denominator_imaginary;
+
+        (
+            (numerator_real * denominator_real + numerator_imaginary * denominator_imaginary)
+                / denominator_squared,
+            (numerator_imaginary * denominator_real - numerator_real * denominator_imaginary)
+                / denominator_squared,
+        )
+    }
+}
+
+/// A topology preserving transform (Zavalishin) state variable filter
+///
+/// Unlike the biquad in `Filter`, its state doesn't depend on the coefficients, so the cutoff
+/// can be swept at audio rate (fast envelopes, lfos) without blowing up or zippering.
+///
+/// # Inputs
+/// `in` - Audio in.
+/// `filter_offset` - Cutoff offset in octaves.
+///
+/// # Outputs
+/// `lowpass`, `bandpass`, `highpass`, `notch` - All computed every sample.
+/// `out` - Same as `lowpass`.
+pub struct StateVariableFilter {
+    frequency: f32,
+    q: f32,
+    dirty: bool,
+    g: f32,
+    k: f32,
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    ic1eq: f32, // integrator states
+    ic2eq: f32,
+    prev_offset: f32,
+    filter_offset_in: f32,
+    input_in: f32,
+    output_lowpass: f32,
+    output_bandpass: f32,
+    output_highpass: f32,
+    output_notch: f32,
+}
+
+impl StateVariableFilter {
+    pub fn new(frequency: f32, q: f32) -> StateVariableFilter {
+        let mut new_filter = StateVariableFilter {
+            frequency,
+            q,
+            dirty: true,
+            g: 0.0,
+            k: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            a3: 0.0,
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+            prev_offset: 0.0,
+            filter_offset_in: 0.0,
+            input_in: 0_f32,
+            output_lowpass: 0_f32,
+            output_bandpass: 0_f32,
+            output_highpass: 0_f32,
+            output_notch: 0_f32,
+        };
+
+        new_filter.recompute();
+
+        new_filter
+    }
+
+    /// cutoff with the offset applied, clamped to keep the tan below infinity
+    fn cutoff(&self) -> f32 {
+        (self.frequency * f32::powf(2.0, self.filter_offset_in)).clamp(0.01, SAMPLE_RATE as f32 * 0.49)
+    }
+
+    fn recompute(&mut self) {
+        self.g = (PI * self.cutoff() / SAMPLE_RATE as f32).tan();
+        self.k = 1.0 / self.q;
+        self.a1 = 1.0 / (1.0 + self.g * (self.g + self.k));
+        self.a2 = self.g * self.a1;
+        self.a3 = self.g * self.a2;
+
+        self.dirty = false;
+    }
+
+    pub fn get_frequency(&self) -> f32 {
+        self.frequency
+    }
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.dirty = true;
+        self.frequency = frequency;
+    }
+
+    pub fn get_q(&self) -> f32 {
+        self.q
+    }
+    pub fn set_q(&mut self, q: f32) {
+        self.dirty = true;
+        self.q = q;
+    }
+
+    /// the response of one output (`lowpass`, `bandpass`, `highpass`, `notch` or `out`), at the
+    /// current cutoff offset
+    pub fn response(&self, output_type: OutputType) -> Result<SvfResponse, SimpleError> {
+        let output = match output_type {
+            OutputType::Out | OutputType::Lowpass => SvfOutput::Lowpass,
+            OutputType::Bandpass => SvfOutput::Bandpass,
+            OutputType::Highpass => SvfOutput::Highpass,
+            OutputType::Notch => SvfOutput::Notch,
+            _ => bail!("Cannot output {:?}", output_type),
+        };
+
+        Ok(SvfResponse {
+            g: (PI * self.cutoff() / SAMPLE_RATE as f32).tan(),
+            k: 1.0 / self.q,
+            output,
+        })
+    }
+}
+
+impl AudioNode for StateVariableFilter {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::FilterOffset => self.filter_offset_in = input,
+            InputType::In => self.input_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        if f32::abs(self.filter_offset_in - self.prev_offset) > f32::EPSILON || self.dirty {
+            // avoid excess recomputation
+            self.recompute();
+        }
+
+        let v3 = self.input_in - self.ic2eq;
+        let v1 = self.a1 * self.ic1eq + self.a2 * v3;
+        let v2 = self.ic2eq + self.a2 * self.ic1eq + self.a3 * v3;
+
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        self.output_lowpass = v2;
+        self.output_bandpass = self.k * v1; // normalized to a 0 dB peak
+        self.output_highpass = self.input_in - self.k * v1 - v2;
+        self.output_notch = self.output_lowpass + self.output_highpass;
+
+        self.prev_offset = self.filter_offset_in;
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out | OutputType::Lowpass => Ok(self.output_lowpass),
+            OutputType::Bandpass => Ok(self.output_bandpass),
+            OutputType::Highpass => Ok(self.output_highpass),
+            OutputType::Notch => Ok(self.output_notch),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
+
+impl FrequencyResponse for StateVariableFilter {
+    /// response of `out` (the lowpass), see `response` for the others
+    fn complex_response(&self, frequency: f32) -> (f32, f32) {
+        self.response(OutputType::Out).unwrap().complex_response(frequency)
+    }
+}
@@ -0,0 +1,175 @@
+use simple_error::bail;
+use simple_error::SimpleError;
+
+use crate::node::filter::FilterType;
+use crate::node::{AudioNode, EnvelopeFollower, Filter, InputType, OutputType};
+use crate::util::from_decibels;
+use crate::util::random::Random;
+
+pub const MIN_BANDS: usize = 8;
+pub const MAX_BANDS: usize = 32;
+
+// centers of the lowest and highest bands
+const LOWEST_BAND: f32 = 100.0;
+const HIGHEST_BAND: f32 = 8_000.0;
+
+const FOLLOWER_ATTACK: f32 = 0.002;
+const FOLLOWER_RELEASE: f32 = 0.02;
+
+/// One analysis and synthesis pair
+struct VocoderBand {
+    frequency: f32,
+    analysis: Filter,
+    follower: EnvelopeFollower,
+    synthesis: Filter,
+}
+
+impl VocoderBand {
+    fn process(&mut self, modulator: f32, carrier: f32) -> f32 {
+        self.analysis.receive_audio(InputType::In, modulator).unwrap();
+        self.analysis.process();
+
+        self.follower.receive_audio(InputType::In, self.analysis.get_output_audio(OutputType::Out).unwrap()).unwrap();
+        self.follower.process();
+
+        self.synthesis.receive_audio(InputType::In, carrier).unwrap();
+        self.synthesis.process();
+
+        self.synthesis.get_output_audio(OutputType::Out).unwrap()
+            * self.follower.get_output_audio(OutputType::Out).unwrap()
+    }
+}
+
+/// A channel vocoder, making the carrier speak with the modulator's spectrum
+///
+/// The modulator is split into bands spaced evenly in pitch between 100hz and 8khz, and the
+/// level of each band sets the level of the same band of the carrier. Bright carriers (like a
+/// saw from a `MidiOscillator`) work best, and mixing in noise helps consonants come through.
+///
+/// # Inputs
+/// `carrier` - Audio to shape, usually a synth.
+/// `modulator` - Audio to follow, usually a voice.
+///
+/// # Outputs
+/// `out` - Audio out.
+pub struct Vocoder {
+    bands: Vec<VocoderBand>,
+    formant_shift: f32, // in semitones
+    noise: f32,         // level of noise added to the carrier
+    output_gain: f32,   // in decibels
+    random: Random,
+    carrier_in: f32,
+    modulator_in: f32,
+    output_out: f32,
+}
+
+impl Vocoder {
+    pub fn new(bands: usize) -> Vocoder {
+        let mut new_vocoder = Vocoder {
+            bands: vec![],
+            formant_shift: 0.0,
+            noise: 0.0,
+            output_gain: 12.0,
+            random: Random::default(),
+            carrier_in: 0_f32,
+            modulator_in: 0_f32,
+            output_out: 0_f32,
+        };
+
+        new_vocoder.set_bands(bands);
+
+        new_vocoder
+    }
+
+    pub fn get_bands(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// between `MIN_BANDS` and `MAX_BANDS`, more bands are clearer but cost more
+    pub fn set_bands(&mut self, bands: usize) {
+        let bands = bands.clamp(MIN_BANDS, MAX_BANDS);
+
+        // each band covers the gap between its neighbours
+        let ratio = f32::powf(HIGHEST_BAND / LOWEST_BAND, 1.0 / (bands - 1) as f32);
+        let q = ratio.sqrt() / (ratio - 1.0);
+
+        self.bands = (0..bands)
+            .map(|band| {
+                let frequency = LOWEST_BAND * ratio.powi(band as i32);
+
+                VocoderBand {
+                    frequency,
+                    analysis: Filter::new(FilterType::BandpassConstantPeak, frequency, q),
+                    follower: EnvelopeFollower::new(FOLLOWER_ATTACK, FOLLOWER_RELEASE),
+                    synthesis: Filter::new(FilterType::BandpassConstantPeak, frequency, q),
+                }
+            })
+            .collect();
+
+        self.set_formant_shift(self.formant_shift);
+    }
+
+    pub fn get_formant_shift(&self) -> f32 {
+        self.formant_shift
+    }
+
+    /// moves the carrier's bands up or down by up to an octave (in semitones), for a smaller or
+    /// bigger sounding voice
+    pub fn set_formant_shift(&mut self, formant_shift: f32) {
+        self.formant_shift = formant_shift.clamp(-12.0, 12.0);
+
+        let shift = f32::powf(2.0, self.formant_shift / 12.0);
+        for band in self.bands.iter_mut() {
+            band.synthesis.set_frequency(band.frequency * shift);
+        }
+    }
+
+    pub fn get_noise(&self) -> f32 {
+        self.noise
+    }
+
+    /// how much white noise to mix into the carrier, between 0 and 1
+    pub fn set_noise(&mut self, noise: f32) {
+        self.noise = noise.clamp(0.0, 1.0);
+    }
+
+    pub fn get_output_gain(&self) -> f32 {
+        self.output_gain
+    }
+
+    /// in decibels, to make up for the level lost to splitting everything into narrow bands
+    pub fn set_output_gain(&mut self, output_gain: f32) {
+        self.output_gain = output_gain;
+    }
+}
+
+impl AudioNode for Vocoder {
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::Carrier => self.carrier_in = input,
+            InputType::Modulator => self.modulator_in = input,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
+    }
+
+    fn process(&mut self) {
+        let carrier = self.carrier_in + self.random.next_bipolar() * self.noise;
+
+        let output: f32 = self
+            .bands
+            .iter_mut()
+            .map(|band| band.process(self.modulator_in, carrier))
+            .sum();
+
+        self.output_out = output * from_decibels(self.output_gain);
+    }
+
+    fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
+        match output_type {
+            OutputType::Out => Ok(self.output_out),
+            _ => bail!("Cannot output {:?}", output_type),
+        }
+    }
+}
@@ -3,31 +3,140 @@ use crate::node::{AudioNode, MidiNode, InputType, OutputType};
 use simple_error::bail;
 use simple_error::SimpleError;
 
-use crate::node::{Envelope, Gain, oscillator::Waveform};
+use crate::node::{Envelope, Filter, RingModulator, envelope::{EnvelopeCurve, EnvelopeGenerator, EnvelopeState, RetriggerStart, TriggerMode}, filter::FilterType, oscillator::{PhaseMode, Waveform}, ring_modulator::CarrierSource};
 use crate::pipeline::ramped_oscillator::RampedOscillator;
 use crate::midi::messages::MidiData;
+use crate::wave::interpolate::Interpolation;
 
-pub struct MidiOscillator {
+/// A monophonic synth voice: an oscillator through a filter and an amplifier, each with an
+/// envelope
+///
+/// `Envelope` by default, but any `EnvelopeGenerator` works (see `with_envelopes`).
+pub struct MidiOscillator<E: EnvelopeGenerator = Envelope> {
     midi_in: Vec<MidiData>,
-    envelope: Envelope,
+    envelope: E,
+    filter_envelope: E,
     osc: RampedOscillator,
-    gain: Gain,
+    filter: Filter,
+    amplifier: RingModulator, // multiplies by the envelope at audio rate
     output_out: f32,
-    gate: bool
+    gate: bool,
+    trigger: bool, // set for one sample on every note on
+    note: f32,
+    velocity: f32, // between 0 and 1
+    held_notes: Vec<(u8, f32)>, // notes still held down with their velocity, latest last
 }
 
 impl MidiOscillator {
     pub fn new() -> MidiOscillator {
+        MidiOscillator::with_envelopes(
+            Envelope::new(0.01, 0.2, 1.0, 0.3),
+            Envelope::new(0.01, 0.2, 1.0, 0.3),
+        )
+    }
+
+    pub fn get_adsr(&self) -> (f32, f32, f32, f32) {
+        self.envelope.get_adsr()
+    }
+
+    pub fn set_adsr(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
+        self.envelope.set_adsr(attack, decay, sustain, release);
+    }
+
+    pub fn get_curves(&self) -> (EnvelopeCurve, EnvelopeCurve, EnvelopeCurve) {
+        self.envelope.get_curves()
+    }
+
+    pub fn set_curves(&mut self, attack: EnvelopeCurve, decay: EnvelopeCurve, release: EnvelopeCurve) {
+        self.envelope.set_curves(attack, decay, release);
+    }
+
+    pub fn get_velocity_attack_sensitivity(&self) -> f32 {
+        self.envelope.get_velocity_attack_sensitivity()
+    }
+
+    /// how much velocity shortens the attack of both envelopes, between 0 and 1
+    pub fn set_velocity_attack_sensitivity(&mut self, velocity_attack_sensitivity: f32) {
+        self.envelope.set_velocity_attack_sensitivity(velocity_attack_sensitivity);
+        self.filter_envelope.set_velocity_attack_sensitivity(velocity_attack_sensitivity);
+    }
+
+    pub fn get_key_follow(&self) -> f32 {
+        self.envelope.get_key_follow()
+    }
+
+    /// how much higher notes shorten the decay and release of both envelopes
+    pub fn set_key_follow(&mut self, key_follow: f32) {
+        self.envelope.set_key_follow(key_follow);
+        self.filter_envelope.set_key_follow(key_follow);
+    }
+
+    pub fn get_filter_adsr(&self) -> (f32, f32, f32, f32) {
+        self.filter_envelope.get_adsr()
+    }
+
+    pub fn set_filter_adsr(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
+        self.filter_envelope.set_adsr(attack, decay, sustain, release);
+    }
+
+    pub fn get_filter_curves(&self) -> (EnvelopeCurve, EnvelopeCurve, EnvelopeCurve) {
+        self.filter_envelope.get_curves()
+    }
+
+    pub fn set_filter_curves(&mut self, attack: EnvelopeCurve, decay: EnvelopeCurve, release: EnvelopeCurve) {
+        self.filter_envelope.set_curves(attack, decay, release);
+    }
+
+    pub fn get_retrigger_start(&self) -> RetriggerStart {
+        self.envelope.get_retrigger_start()
+    }
+
+    pub fn set_retrigger_start(&mut self, retrigger_start: RetriggerStart) {
+        self.envelope.set_retrigger_start(retrigger_start);
+        self.filter_envelope.set_retrigger_start(retrigger_start);
+    }
+
+    pub fn get_envelope_state(&self) -> EnvelopeState {
+        self.envelope.get_state()
+    }
+}
+
+impl<E: EnvelopeGenerator> MidiOscillator<E> {
+    /// a voice shaped by any kind of envelope, like a pair of `MultiStageEnvelope`s
+    pub fn with_envelopes(envelope: E, filter_envelope: E) -> MidiOscillator<E> {
         MidiOscillator {
             midi_in: Vec::new(),
-            envelope: Envelope::new(0.01, 0.2, 1.0, 0.3),
+            envelope,
+            filter_envelope,
             osc: RampedOscillator::new(),
-            gain: Gain::new(),
+            // wide open until told otherwise
+            filter: Filter::new(FilterType::Lowpass, 20_000.0, 0.707),
+            amplifier: RingModulator::new(CarrierSource::External),
             output_out: 0.0,
-            gate: false
+            gate: false,
+            trigger: false,
+            note: 69.0,
+            velocity: 1.0,
+            held_notes: Vec::new(),
         }
     }
 
+    pub fn get_envelope(&self) -> &E {
+        &self.envelope
+    }
+
+    pub fn get_envelope_mut(&mut self) -> &mut E {
+        &mut self.envelope
+    }
+
+    pub fn get_filter_envelope(&self) -> &E {
+        &self.filter_envelope
+    }
+
+    pub fn get_filter_envelope_mut(&mut self) -> &mut E {
+        &mut self.filter_envelope
+    }
+
     pub fn set_portamento(&mut self, portamento: f32) {
         self.osc.set_portamento(portamento);
     }
@@ -36,24 +145,113 @@ impl MidiOscillator {
         self.osc.get_portamento()
     }
 
-    pub fn get_adsr(&self) -> (f32, f32, f32, f32) {
-        self.envelope.get_adsr()
+    pub fn get_velocity_sensitivity(&self) -> f32 {
+        self.envelope.get_velocity_sensitivity()
     }
 
-    pub fn set_adsr(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
-        self.envelope.set_adsr(attack, decay, sustain, release);
+    /// how much velocity scales the volume, between 0 and 1
+    pub fn set_velocity_sensitivity(&mut self, velocity_sensitivity: f32) {
+        self.envelope.set_velocity_sensitivity(velocity_sensitivity);
+    }
+
+    pub fn get_filter_type(&self) -> FilterType {
+        self.filter.get_filter_type()
+    }
+
+    pub fn set_filter_type(&mut self, filter_type: FilterType) {
+        self.filter.set_filter_type(filter_type);
+    }
+
+    pub fn get_filter_frequency(&self) -> f32 {
+        self.filter.get_frequency()
+    }
+
+    pub fn set_filter_frequency(&mut self, frequency: f32) {
+        self.filter.set_frequency(frequency);
+    }
+
+    pub fn get_filter_q(&self) -> f32 {
+        self.filter.get_q()
+    }
+
+    pub fn set_filter_q(&mut self, q: f32) {
+        self.filter.set_q(q);
+    }
+
+    pub fn get_filter_envelope_amount(&self) -> f32 {
+        self.filter.get_envelope_amount()
+    }
+
+    /// in octaves
+    pub fn set_filter_envelope_amount(&mut self, envelope_amount: f32) {
+        self.filter.set_envelope_amount(envelope_amount);
+    }
+
+    pub fn get_filter_key_tracking(&self) -> f32 {
+        self.filter.get_key_tracking()
+    }
+
+    pub fn set_filter_key_tracking(&mut self, key_tracking: f32) {
+        self.filter.set_key_tracking(key_tracking);
+    }
+
+    pub fn get_filter_velocity_sensitivity(&self) -> f32 {
+        self.filter.get_velocity_sensitivity()
+    }
+
+    pub fn set_filter_velocity_sensitivity(&mut self, velocity_sensitivity: f32) {
+        self.filter.set_velocity_sensitivity(velocity_sensitivity);
     }
 
     pub fn set_waveform(&mut self, waveform: Waveform) {
         self.osc.set_waveform(waveform);
     }
 
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.osc.set_interpolation(interpolation);
+    }
+
+    pub fn get_interpolation(&self) -> Interpolation {
+        self.osc.get_interpolation()
+    }
+
+    pub fn set_phase_mode(&mut self, phase_mode: PhaseMode) {
+        self.osc.set_phase_mode(phase_mode);
+    }
+
+    pub fn get_phase_mode(&self) -> PhaseMode {
+        self.osc.get_phase_mode()
+    }
+
+    pub fn set_start_phase(&mut self, start_phase: f32) {
+        self.osc.set_start_phase(start_phase);
+    }
+
+    pub fn get_start_phase(&self) -> f32 {
+        self.osc.get_start_phase()
+    }
+
     pub fn get_gate(&self) -> bool {
         self.gate
     }
+
+    pub fn get_trigger_mode(&self) -> TriggerMode {
+        self.envelope.get_trigger_mode()
+    }
+
+    /// whether overlapping notes restart both envelopes
+    pub fn set_trigger_mode(&mut self, trigger_mode: TriggerMode) {
+        self.envelope.set_trigger_mode(trigger_mode);
+        self.filter_envelope.set_trigger_mode(trigger_mode);
+    }
+
+    /// whether the voice has been released and is now silent
+    pub fn is_finished(&self) -> bool {
+        self.envelope.is_finished()
+    }
 }
 
-impl AudioNode for MidiOscillator {
+impl<E: EnvelopeGenerator> AudioNode for MidiOscillator<E> {
     fn receive_audio(&mut self, input_type: InputType, _input: f32) -> Result<(), SimpleError> {
         bail!("Cannot receive {:?}", input_type);
     }
@@ -62,39 +260,76 @@ impl AudioNode for MidiOscillator {
         if !self.midi_in.is_empty() {
             for message in self.midi_in.iter_mut() {
                 match message {
-                    MidiData::NoteOn {note, ..} => {
+                    MidiData::NoteOn {note, velocity, ..} => {
+                        let velocity = *velocity as f32 / 127.0;
+
+                        self.held_notes.retain(|(held, _)| held != note);
+                        self.held_notes.push((*note, velocity));
+
                         self.gate = true;
+                        self.trigger = true;
+                        self.note = *note as f32;
+                        self.velocity = velocity;
                         self.osc.set_frequency(440.0 * f32::powf(2.0, (*note as f32 - 69.0) / 12.0));
                     }
-                    MidiData::NoteOff {..} => {
-                        self.gate = false;
+                    MidiData::NoteOff {note, ..} => {
+                        self.held_notes.retain(|(held, _)| held != note);
+
+                        // letting go of the playing note goes back to the last one still held
+                        match self.held_notes.last() {
+                            None => self.gate = false,
+                            Some(&(held, velocity)) if held as f32 != self.note => {
+                                self.trigger = true;
+                                self.note = held as f32;
+                                self.velocity = velocity;
+                                self.osc.set_frequency(440.0 * f32::powf(2.0, (held as f32 - 69.0) / 12.0));
+                            }
+                            Some(_) => {}
+                        }
                     }
                     _ => {}
                 }
             }
         }
 
+        let gate = if self.gate {1.0} else {0.0};
+        let trigger = if self.trigger {1.0} else {0.0};
+        self.trigger = false;
+
+        self.osc.receive_audio(InputType::Gate, gate).unwrap();
         self.osc.process();
 
-        self.envelope.receive_audio(InputType::Gate, if self.gate {1.0} else {0.0}).unwrap();
-        self.envelope.process();
+        for envelope in [&mut self.envelope, &mut self.filter_envelope] {
+            envelope.receive_audio(InputType::Gate, gate).unwrap();
+            envelope.receive_audio(InputType::Trigger, trigger).unwrap();
+            envelope.receive_audio(InputType::Note, self.note).unwrap();
+            envelope.receive_audio(InputType::Velocity, self.velocity).unwrap();
+            envelope.process();
+        }
+
+        self.filter.receive_audio(InputType::In, self.osc.get_output_audio(OutputType::Out).unwrap()).unwrap();
+        self.filter.receive_audio(InputType::Note, self.note).unwrap();
+        self.filter.receive_audio(InputType::Velocity, self.velocity).unwrap();
+        self.filter.receive_audio(InputType::Envelope, self.filter_envelope.get_output_audio(OutputType::Out).unwrap()).unwrap();
+        self.filter.process();
 
-        self.gain.receive_audio(InputType::In, self.osc.get_output_audio(OutputType::Out).unwrap()).unwrap();
-        self.gain.set_gain(self.envelope.get_output_audio(OutputType::Out).unwrap());
-        self.gain.process();
+        self.amplifier.receive_audio(InputType::Modulator, self.filter.get_output_audio(OutputType::Out).unwrap()).unwrap();
+        self.amplifier.receive_audio(InputType::Carrier, self.envelope.get_output_audio(OutputType::Out).unwrap()).unwrap();
+        self.amplifier.process();
 
-        self.output_out = self.gain.get_output_audio(OutputType::Out).unwrap();
+        self.output_out = self.amplifier.get_output_audio(OutputType::Out).unwrap();
     }
 
     fn get_output_audio(&self, output_type: OutputType) -> Result<f32, SimpleError> {
         match output_type {
             OutputType::Out => Ok(self.output_out),
+            OutputType::Finished => self.envelope.get_output_audio(OutputType::Finished),
             _ => bail!("Cannot output {:?}", output_type),
         }
     }
 }
 
-impl MidiNode for MidiOscillator {
+impl<E: EnvelopeGenerator> MidiNode for MidiOscillator<E> {
     fn receive_midi(&mut self, input_type: InputType, input: &Vec<MidiData>) -> Result<(), SimpleError> {
         match input_type {
             InputType::In => self.midi_in.clone_from(input),
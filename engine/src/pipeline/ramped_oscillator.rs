@@ -1,5 +1,6 @@
 use crate::node::oscillator::Oscillator;
-use crate::node::{AudioNode, InputType, OutputType, Ramp, ramp::RampType, OscillatorNode, oscillator::Waveform};
+use crate::node::{AudioNode, InputType, OutputType, Ramp, ramp::RampType, OscillatorNode, oscillator::{PhaseMode, Waveform}};
+use crate::wave::interpolate::Interpolation;
 
 use simple_error::bail;
 use simple_error::SimpleError;
@@ -45,11 +46,40 @@ impl RampedOscillator {
     pub fn set_waveform(&mut self, waveform: Waveform) {
         self.oscillator.set_waveform(waveform);
     }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.oscillator.set_interpolation(interpolation);
+    }
+
+    pub fn get_interpolation(&self) -> Interpolation {
+        self.oscillator.get_interpolation()
+    }
+
+    pub fn set_phase_mode(&mut self, phase_mode: PhaseMode) {
+        self.oscillator.set_phase_mode(phase_mode);
+    }
+
+    pub fn get_phase_mode(&self) -> PhaseMode {
+        self.oscillator.get_phase_mode()
+    }
+
+    pub fn set_start_phase(&mut self, start_phase: f32) {
+        self.oscillator.set_start_phase(start_phase);
+    }
+
+    pub fn get_start_phase(&self) -> f32 {
+        self.oscillator.get_start_phase()
+    }
 }
 
 impl AudioNode for RampedOscillator {
-    fn receive_audio(&mut self, input_type: InputType, _input: f32) -> Result<(), SimpleError> {
-        bail!("Cannot receive {:?}", input_type);
+    fn receive_audio(&mut self, input_type: InputType, input: f32) -> Result<(), SimpleError> {
+        match input_type {
+            InputType::Gate => self.oscillator.receive_audio(InputType::Gate, input)?,
+            _ => bail!("Cannot receive {:?}", input_type),
+        }
+
+        Ok(())
     }
 
     fn process(&mut self) {
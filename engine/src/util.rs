@@ -1,3 +1,41 @@
+pub mod delay_line;
+pub mod random;
+pub mod tempo;
+
+use crate::constants::SAMPLE_RATE;
+
+/// quietest level `to_decibels` goes down to
+pub const MIN_DECIBELS: f32 = -96.0;
+
 pub fn lerp(start: f32, end: f32, amount: f32) -> f32 {
     (end - start) * amount + start
 }
+
+/// 4-point cubic Hermite interpolation between `start` and `end`
+pub fn hermite(before: f32, start: f32, end: f32, after: f32, amount: f32) -> f32 {
+    let c1 = 0.5 * (end - before);
+    let c2 = before - 2.5 * start + 2.0 * end - 0.5 * after;
+    let c3 = 0.5 * (after - before) + 1.5 * (start - end);
+
+    ((c3 * amount + c2) * amount + c1) * amount + start
+}
+
+/// per sample coefficient for a one pole smoother to get about 63% of the way there in `time`
+/// seconds
+pub fn smoothing_coefficient(time: f32) -> f32 {
+    if time > 0.0 {
+        f32::exp(-1.0 / (time * SAMPLE_RATE as f32))
+    } else {
+        0.0
+    }
+}
+
+/// linear gain to decibels, floored at `MIN_DECIBELS`
+pub fn to_decibels(gain: f32) -> f32 {
+    (20.0 * gain.abs().log10()).max(MIN_DECIBELS)
+}
+
+/// decibels to linear gain
+pub fn from_decibels(decibels: f32) -> f32 {
+    f32::powf(10.0, decibels / 20.0)
+}
@@ -0,0 +1,60 @@
+use crate::constants::SAMPLE_RATE;
+use crate::util::hermite;
+
+/// A ring buffer that can be read at any fractional number of samples in the past
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    write_index: usize, // where the next sample goes
+}
+
+impl DelayLine {
+    /// `max_delay` is in seconds
+    pub fn new(max_delay: f32) -> DelayLine {
+        // room for the interpolation points either side of the longest delay
+        let length = (max_delay * SAMPLE_RATE as f32).ceil() as usize + 4;
+
+        DelayLine {
+            buffer: vec![0.0; length],
+            write_index: 0,
+        }
+    }
+
+    /// longest delay that can be read, in samples
+    pub fn max_delay_samples(&self) -> f32 {
+        (self.buffer.len() - 4) as f32
+    }
+
+    pub fn write(&mut self, sample: f32) {
+        self.buffer[self.write_index] = sample;
+        self.write_index = (self.write_index + 1) % self.buffer.len();
+    }
+
+    /// reads `delay` samples behind the most recently written sample, clamped to what fits
+    pub fn read(&self, delay: f32) -> f32 {
+        let delay = delay.clamp(0.0, self.max_delay_samples());
+
+        let whole = delay as usize;
+        let fraction = delay - whole as f32;
+
+        // going backwards in time, so `fraction` moves from `at(whole)` towards `at(whole + 1)`
+        hermite(
+            self.at((whole as isize - 1).max(0)), // nothing newer than the newest sample
+            self.at(whole as isize),
+            self.at(whole as isize + 1),
+            self.at(whole as isize + 2),
+            fraction,
+        )
+    }
+
+    /// `delay` whole samples behind the most recently written sample
+    fn at(&self, delay: isize) -> f32 {
+        let length = self.buffer.len() as isize;
+        let index = (self.write_index as isize - 1 - delay).rem_euclid(length);
+
+        self.buffer[index as usize]
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.iter_mut().for_each(|sample| *sample = 0.0);
+    }
+}
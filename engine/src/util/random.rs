@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+static SEED_COUNTER: AtomicU32 = AtomicU32::new(0x9E37_79B9);
+
+/// A small xorshift generator for audio purposes (noise, random phases, sample and hold)
+///
+/// Not suitable for anything that needs real randomness.
+pub struct Random {
+    state: u32,
+}
+
+impl Random {
+    pub fn new(seed: u32) -> Random {
+        Random {
+            // xorshift gets stuck at zero
+            state: if seed == 0 { 0x2545_F491 } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        x
+    }
+
+    /// between 0 and 1
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// between -1 and 1
+    pub fn next_bipolar(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
+    }
+}
+
+impl Default for Random {
+    /// every default generator gets a different seed, so separate voices don't move in lockstep
+    fn default() -> Self {
+        Random::new(SEED_COUNTER.fetch_add(0x6D2B_79F5, Ordering::Relaxed))
+    }
+}
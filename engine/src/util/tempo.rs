@@ -0,0 +1,56 @@
+/// Note values used for tempo synced times
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteDivision {
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoteModifier {
+    Straight,
+    Dotted,
+    Triplet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteLength {
+    pub division: NoteDivision,
+    pub modifier: NoteModifier,
+}
+
+impl NoteLength {
+    pub fn new(division: NoteDivision, modifier: NoteModifier) -> NoteLength {
+        NoteLength { division, modifier }
+    }
+
+    /// length in quarter note beats
+    pub fn beats(&self) -> f32 {
+        let straight = match self.division {
+            NoteDivision::Whole => 4.0,
+            NoteDivision::Half => 2.0,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::Sixteenth => 0.25,
+            NoteDivision::ThirtySecond => 0.125,
+        };
+
+        match self.modifier {
+            NoteModifier::Straight => straight,
+            NoteModifier::Dotted => straight * 1.5,
+            NoteModifier::Triplet => straight * 2.0 / 3.0,
+        }
+    }
+
+    pub fn seconds(&self, bpm: f32) -> f32 {
+        self.beats() * 60.0 / bpm
+    }
+
+    /// how many times per second this note length repeats
+    pub fn frequency(&self, bpm: f32) -> f32 {
+        1.0 / self.seconds(bpm)
+    }
+}
@@ -1,31 +1,114 @@
-use crate::constants::TWO_PI;
-use crate::util::lerp;
+use crate::constants::{PI, TWO_PI};
+use crate::util::{hermite, lerp};
 
 use super::tables::{BASE_FREQUENCY, WAVETABLE_SIZE};
 
+const SINC_TAPS: usize = 8;
+const SINC_RESOLUTION: usize = 64;
+
+/// How samples within a wavetable are reconstructed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    /// Straight line between the two nearest samples (cheapest)
+    Linear,
+    /// 4-point cubic Hermite
+    Cubic,
+    /// 8-point Blackman windowed sinc (most accurate, most expensive)
+    Sinc,
+}
+
+lazy_static! {
+    // one row of kernel weights for each fractional offset between 0 and 1 (inclusive)
+    static ref SINC_KERNEL: Vec<[f32; SINC_TAPS]> = {
+        let half_width = (SINC_TAPS / 2) as f32;
+        let mut kernel = vec![[0_f32; SINC_TAPS]; SINC_RESOLUTION + 1];
+
+        for (row_index, row) in kernel.iter_mut().enumerate() {
+            let offset = row_index as f32 / SINC_RESOLUTION as f32;
+
+            for (tap, weight) in row.iter_mut().enumerate() {
+                // distance from the point being read, taps start 3 samples behind it
+                let x = tap as f32 - (half_width - 1.0) - offset;
+
+                let sinc = if x.abs() < f32::EPSILON {
+                    1.0
+                } else {
+                    f32::sin(PI * x) / (PI * x)
+                };
+
+                let window = 0.42
+                    + 0.5 * f32::cos(PI * x / half_width)
+                    + 0.08 * f32::cos(TWO_PI * x / half_width);
+
+                *weight = sinc * window;
+            }
+
+            // normalize so that a constant signal comes out unchanged
+            let sum: f32 = row.iter().sum();
+            for weight in row.iter_mut() {
+                *weight /= sum;
+            }
+        }
+
+        kernel
+    };
+}
+
+/// Reads `wavetable` at `phase` (in radians), choosing and blending the band limited tables
+/// that fit `frequency`
+///
+/// Negative frequencies (such as from through-zero FM) use the tables of their absolute value,
+/// and frequencies above the highest table are read from the highest table.
 pub fn interpolate(
     wavetable: &Vec<[f32; WAVETABLE_SIZE]>,
     frequency: f32,
     phase: f32,
+    quality: Interpolation,
 ) -> f32 {
-    let phase = (phase / TWO_PI) % 1.0; // make phase bound
+    let phase = (phase / TWO_PI).rem_euclid(1.0); // make phase bound
+    let position = phase * WAVETABLE_SIZE as f32;
 
-    let wavetable_index = (frequency / BASE_FREQUENCY) as usize; // which wavetable to use (rounded down)
-    let sample_index = (phase * WAVETABLE_SIZE as f32) as usize; // which sample
-    let sample_offset = (phase * WAVETABLE_SIZE as f32) % 1.0; // interpolate between samples
+    let last_index = wavetable.len() - 1;
+    let table_position = (frequency.abs() / BASE_FREQUENCY).min(last_index as f32);
 
-    let lower_old = wavetable[wavetable_index][sample_index];
-    let lower_new = wavetable[wavetable_index][(sample_index + 1) % WAVETABLE_SIZE];
+    let wavetable_index = table_position as usize; // which wavetable to use (rounded down)
+    let upper_index = (wavetable_index + 1).min(last_index);
 
-    let upper_old = wavetable[wavetable_index + 1][sample_index];
-    let upper_new = wavetable[wavetable_index + 1][(sample_index + 1) % WAVETABLE_SIZE];
+    let sample_lower = read_table(&wavetable[wavetable_index], position, quality);
 
-    let sample_lower = lerp(lower_old, lower_new, sample_offset);
-    let sample_higher = lerp(upper_old, upper_new, sample_offset);
+    if upper_index == wavetable_index {
+        return sample_lower;
+    }
+
+    let sample_higher = read_table(&wavetable[upper_index], position, quality);
 
     lerp(
         sample_lower,
         sample_higher,
-        (frequency - (BASE_FREQUENCY * (wavetable_index) as f32)) / BASE_FREQUENCY
+        table_position - wavetable_index as f32,
     )
 }
+
+/// `position` is in samples, between 0 and `WAVETABLE_SIZE`
+fn read_table(table: &[f32; WAVETABLE_SIZE], position: f32, quality: Interpolation) -> f32 {
+    let sample_index = position as usize % WAVETABLE_SIZE; // which sample
+    let sample_offset = position % 1.0; // interpolate between samples
+
+    let at = |offset: isize| -> f32 {
+        table[(sample_index as isize + offset).rem_euclid(WAVETABLE_SIZE as isize) as usize]
+    };
+
+    match quality {
+        Interpolation::Linear => lerp(at(0), at(1), sample_offset),
+        Interpolation::Cubic => hermite(at(-1), at(0), at(1), at(2), sample_offset),
+        Interpolation::Sinc => {
+            let row = &SINC_KERNEL[(sample_offset * SINC_RESOLUTION as f32).round() as usize];
+            let first_tap = 1 - (SINC_TAPS / 2) as isize;
+
+            row.iter()
+                .enumerate()
+                .map(|(tap, weight)| weight * at(first_tap + tap as isize))
+                .sum()
+        }
+    }
+}
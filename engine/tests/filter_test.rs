@@ -0,0 +1,288 @@
+use engine::constants::{SAMPLE_RATE, TWO_PI};
+use engine::node::filter::{Filter, FilterType, FrequencyResponse};
+use engine::node::equalizer::{BandType, EqBand, Equalizer};
+use engine::node::formant_filter::{FormantFilter, Vowel};
+use engine::node::ladder_filter::{LadderFilter, LadderSlope};
+use engine::node::state_variable_filter::StateVariableFilter;
+use engine::node::{AudioNode, InputType, OutputType};
+use simple_error::SimpleError;
+
+/// runs a sine through the filter and returns its amplitude (from the RMS) once it has settled
+fn measure_gain(filter: &mut dyn AudioNode, frequency: f32) -> Result<f32, SimpleError> {
+    let mut sum_of_squares = 0_f32;
+    let mut samples = 0;
+
+    for i in 0..SAMPLE_RATE {
+        let input = (i as f32 / SAMPLE_RATE as f32 * frequency * TWO_PI).sin();
+
+        filter.receive_audio(InputType::In, input)?;
+        filter.process();
+
+        if i >= SAMPLE_RATE / 2 {
+            sum_of_squares += filter.get_output_audio(OutputType::Out)?.powi(2);
+            samples += 1;
+        }
+    }
+
+    Ok((sum_of_squares / samples as f32).sqrt() * 2_f32.sqrt())
+}
+
+fn assert_gain(filter_type: FilterType, gain: f32, expected: &[(f32, f32)]) -> Result<(), SimpleError> {
+    for (frequency, expected_gain) in expected {
+        let mut filter = Filter::new(filter_type, 1_000.0, 0.707);
+        filter.set_gain(gain);
+
+        let measured = measure_gain(&mut filter, *frequency)?;
+
+        assert!(
+            (measured - expected_gain).abs() < 0.05,
+            "{:?} at {} Hz: expected {}, got {}",
+            filter_type,
+            frequency,
+            expected_gain,
+            measured
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn lowpass_and_highpass_response() -> Result<(), SimpleError> {
+    assert_gain(FilterType::Lowpass, 0.0, &[(50.0, 1.0), (1_000.0, 0.707), (16_000.0, 0.0)])?;
+    assert_gain(FilterType::Highpass, 0.0, &[(50.0, 0.0), (1_000.0, 0.707), (16_000.0, 1.0)])
+}
+
+#[test]
+fn bandpass_notch_and_allpass_response() -> Result<(), SimpleError> {
+    assert_gain(FilterType::BandpassConstantPeak, 0.0, &[(1_000.0, 1.0), (20.0, 0.0)])?;
+    assert_gain(FilterType::BandpassConstantSkirt, 0.0, &[(1_000.0, 0.707)])?;
+    assert_gain(FilterType::Notch, 0.0, &[(1_000.0, 0.0), (50.0, 1.0), (16_000.0, 1.0)])?;
+    assert_gain(FilterType::Allpass, 0.0, &[(50.0, 1.0), (1_000.0, 1.0), (16_000.0, 1.0)])
+}
+
+#[test]
+fn peaking_and_shelf_response() -> Result<(), SimpleError> {
+    // +6 dB is just about doubling
+    assert_gain(FilterType::Peaking, 6.0, &[(1_000.0, 1.995), (20.0, 1.0), (20_000.0, 1.0)])?;
+    assert_gain(FilterType::LowShelf, 6.0, &[(20.0, 1.995), (1_000.0, 1.413), (20_000.0, 1.0)])?;
+    assert_gain(FilterType::HighShelf, -6.0, &[(20.0, 1.0), (1_000.0, 0.708), (20_000.0, 0.501)])
+}
+
+#[test]
+fn key_tracking_and_envelope_move_cutoff() -> Result<(), SimpleError> {
+    // an octave up the keyboard with full tracking doubles the cutoff
+    let mut filter = Filter::new(FilterType::Lowpass, 1_000.0, 0.707);
+    filter.set_key_tracking(1.0);
+    filter.receive_audio(InputType::Note, 72.0)?;
+    assert!((measure_gain(&mut filter, 2_000.0)? - 0.707).abs() < 0.02);
+
+    // a full envelope with one octave of amount, at half velocity and full sensitivity
+    let mut filter = Filter::new(FilterType::Lowpass, 1_000.0, 0.707);
+    filter.set_envelope_amount(2.0);
+    filter.set_velocity_sensitivity(1.0);
+    filter.receive_audio(InputType::Velocity, 0.5)?;
+    filter.receive_audio(InputType::Envelope, 1.0)?;
+    assert!((measure_gain(&mut filter, 2_000.0)? - 0.707).abs() < 0.02);
+
+    Ok(())
+}
+
+#[test]
+fn state_variable_filter_outputs_and_fast_sweeps() -> Result<(), SimpleError> {
+    let mut filter = StateVariableFilter::new(1_000.0, 0.707);
+
+    let mut sums = [0_f32; 4];
+    for i in 0..SAMPLE_RATE {
+        filter.receive_audio(InputType::In, (i as f32 / SAMPLE_RATE as f32 * 1_000.0 * TWO_PI).sin())?;
+        filter.process();
+
+        if i >= SAMPLE_RATE / 2 {
+            for (sum, output) in sums.iter_mut().zip([
+                OutputType::Lowpass,
+                OutputType::Bandpass,
+                OutputType::Highpass,
+                OutputType::Notch,
+            ]) {
+                *sum += filter.get_output_audio(output)?.powi(2);
+            }
+        }
+    }
+
+    // at the cutoff, lowpass and highpass are down by q, bandpass is unity and notch is silent
+    let amplitudes: Vec<f32> = sums.iter().map(|sum| (sum / (SAMPLE_RATE / 2) as f32 * 2.0).sqrt()).collect();
+    assert!((amplitudes[0] - 0.707).abs() < 0.02);
+    assert!((amplitudes[1] - 1.0).abs() < 0.02);
+    assert!((amplitudes[2] - 0.707).abs() < 0.02);
+    assert!(amplitudes[3] < 0.02);
+
+    // sweeping several octaves every few samples stays bounded
+    let mut filter = StateVariableFilter::new(1_000.0, 10.0);
+    for i in 0..SAMPLE_RATE {
+        filter.receive_audio(InputType::In, if i % 100 < 50 { 1.0 } else { -1.0 })?;
+        filter.receive_audio(InputType::FilterOffset, (i as f32 * 0.3).sin() * 4.0)?;
+        filter.process();
+
+        assert!(filter.get_output_audio(OutputType::Out)?.abs() < 100.0);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn ladder_filter_self_oscillates() -> Result<(), SimpleError> {
+    let mut filter = LadderFilter::new(1_000.0, 1.1);
+    filter.set_slope(LadderSlope::FourPole);
+
+    // a single click to get it going, then silence
+    filter.receive_audio(InputType::In, 1.0)?;
+    filter.process();
+    filter.receive_audio(InputType::In, 0.0)?;
+
+    let mut peak = 0_f32;
+    for i in 0..SAMPLE_RATE {
+        filter.process();
+
+        if i > SAMPLE_RATE / 2 {
+            peak = peak.max(filter.get_output_audio(OutputType::Out)?.abs());
+        }
+    }
+
+    // still ringing, but held in check by the saturation
+    assert!(peak > 0.05 && peak < 1.0);
+
+    // and with no resonance, a click dies out
+    let mut filter = LadderFilter::new(1_000.0, 0.0);
+    filter.receive_audio(InputType::In, 1.0)?;
+    filter.process();
+    filter.receive_audio(InputType::In, 0.0)?;
+    for _ in 0..SAMPLE_RATE {
+        filter.process();
+    }
+    assert!(filter.get_output_audio(OutputType::Out)?.abs() < 0.0001);
+
+    Ok(())
+}
+
+#[test]
+fn formant_filter_morphs_between_vowels() -> Result<(), SimpleError> {
+    // "a" has its second formant at 1150 Hz, "e" at 2000 Hz
+    let mut filter = FormantFilter::new(Vowel::A);
+    let a_low = measure_gain(&mut filter, 1_150.0)?;
+    let a_high = measure_gain(&mut filter, 2_000.0)?;
+    assert!(a_low > a_high * 4.0);
+
+    // a quarter of the way along is "e"
+    let mut filter = FormantFilter::new(Vowel::A);
+    filter.receive_audio(InputType::Morph, 0.25)?;
+    let e_low = measure_gain(&mut filter, 1_150.0)?;
+    let e_high = measure_gain(&mut filter, 2_000.0)?;
+    assert!(e_high > e_low);
+
+    Ok(())
+}
+
+#[test]
+fn frequency_response_matches_measurement() -> Result<(), SimpleError> {
+    for filter_type in [FilterType::Lowpass, FilterType::Peaking, FilterType::HighShelf] {
+        let mut filter = Filter::new(filter_type, 1_000.0, 2.0);
+        filter.set_gain(6.0);
+        filter.set_key_tracking(1.0);
+        filter.receive_audio(InputType::Note, 48.0)?;
+
+        for frequency in [100.0, 500.0, 2_000.0] {
+            let expected = filter.magnitude_response(frequency);
+            let measured = measure_gain(&mut filter, frequency)?;
+
+            assert!((expected - measured).abs() < 0.02, "{:?} at {} Hz", filter_type, frequency);
+        }
+    }
+
+    // lowpass is -90 degrees at the cutoff
+    let filter = Filter::new(FilterType::Lowpass, 1_000.0, 0.707);
+    assert!((filter.phase_response(1_000.0) + TWO_PI / 4.0).abs() < 0.01);
+
+    let curve = filter.response_curve(20.0, 20_000.0, 31);
+    assert_eq!(curve.len(), 31);
+    assert!((curve[0].frequency - 20.0).abs() < 0.01 && (curve[30].frequency - 20_000.0).abs() < 1.0);
+    assert!((curve[15].frequency - 632.5).abs() < 1.0); // halfway, logarithmically
+
+    Ok(())
+}
+
+#[test]
+fn state_variable_ladder_and_formant_responses() -> Result<(), SimpleError> {
+    // an octave up from the offset, so the response has to include it
+    let mut svf = StateVariableFilter::new(500.0, 2.0);
+    svf.receive_audio(InputType::FilterOffset, 1.0)?;
+
+    for frequency in [100.0, 1_000.0, 5_000.0] {
+        let expected = svf.magnitude_response(frequency);
+        let measured = measure_gain(&mut svf, frequency)?;
+
+        assert!((expected - measured).abs() < 0.02, "svf at {} Hz", frequency);
+    }
+
+    // at the cutoff the bandpass peaks at 1, the notch cancels and the highpass gets q
+    assert!((svf.response(OutputType::Bandpass)?.magnitude_response(1_000.0) - 1.0).abs() < 0.001);
+    assert!(svf.response(OutputType::Notch)?.magnitude_response(1_000.0) < 0.001);
+    assert!((svf.response(OutputType::Highpass)?.magnitude_response(1_000.0) - 2.0).abs() < 0.001);
+    assert!(svf.response(OutputType::Finished).is_err());
+
+    // quiet enough that the saturation doesn't matter
+    for slope in [LadderSlope::TwoPole, LadderSlope::FourPole] {
+        let mut ladder = LadderFilter::new(1_000.0, 0.5);
+        ladder.set_slope(slope);
+        ladder.set_drive(0.01);
+
+        for frequency in [100.0, 1_000.0, 3_000.0] {
+            let expected = ladder.magnitude_response(frequency);
+            let measured = measure_gain(&mut ladder, frequency)?;
+
+            assert!((expected - measured).abs() < expected * 0.05, "{:?} at {} Hz", slope, frequency);
+        }
+    }
+
+    // follows the vowel straight away, without running any audio
+    let mut formant = FormantFilter::new(Vowel::A);
+    formant.set_vowel(Vowel::I);
+    let fresh = FormantFilter::new(Vowel::I);
+
+    for frequency in [270.0, 800.0, 2_140.0] {
+        assert!((formant.magnitude_response(frequency) - fresh.magnitude_response(frequency)).abs() < 0.0001);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn equalizer_combines_bands() -> Result<(), SimpleError> {
+    let mut equalizer = Equalizer::new(vec![
+        EqBand::new(BandType::Highpass, 100.0, 0.0, 0.707),
+        EqBand::new(BandType::Peak, 1_000.0, 6.0, 1.0),
+    ]);
+    equalizer.add_band(EqBand::new(BandType::HighShelf, 8_000.0, -12.0, 0.707));
+
+    let highpass = Filter::new(FilterType::Highpass, 100.0, 0.707);
+    let mut peak = Filter::new(FilterType::Peaking, 1_000.0, 1.0);
+    peak.set_gain(6.0);
+    let mut shelf = Filter::new(FilterType::HighShelf, 8_000.0, 0.707);
+    shelf.set_gain(-12.0);
+
+    // the response is the bands multiplied together, and matches what comes out
+    for frequency in [30.0, 1_000.0, 16_000.0] {
+        let expected = highpass.magnitude_response(frequency)
+            * peak.magnitude_response(frequency)
+            * shelf.magnitude_response(frequency);
+
+        assert!((equalizer.magnitude_response(frequency) - expected).abs() < 0.0001);
+        assert!((measure_gain(&mut equalizer, frequency)? - expected).abs() < 0.02);
+    }
+    assert!((equalizer.magnitude_response(1_000.0) - 2.0).abs() < 0.05);
+
+    equalizer.remove_band(0)?;
+    assert!((equalizer.magnitude_response(30.0) - 1.0).abs() < 0.02);
+    assert!(equalizer.remove_band(2).is_err());
+
+    Ok(())
+}
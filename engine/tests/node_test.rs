@@ -1,7 +1,29 @@
-use engine::node::{ramp::RampType, AudioNode, Dummy, InputType, OutputType, Ramp};
+use engine::node::bitcrusher::Bitcrusher;
+use engine::node::chorus::Chorus;
+use engine::node::compressor::Compressor;
+use engine::node::delay::{Delay, DelayTime};
+use engine::node::distortion::{Distortion, ShaperCurve};
+use engine::node::envelope::{Envelope, EnvelopeCurve, EnvelopeGenerator, EnvelopeState, RetriggerStart, TriggerMode};
+use engine::node::envelope_follower::{Detection, EnvelopeFollower, FollowerScale};
+use engine::node::flanger::Flanger;
+use engine::node::lfo::{Lfo, LfoPolarity, LfoRate, LfoShape};
+use engine::node::limiter::Limiter;
+use engine::node::mixer::{Mixer, PanLaw};
+use engine::node::multi_stage_envelope::{Breakpoint, MultiStageEnvelope};
+use engine::node::oscillator::{OscillatorNode, PhaseMode, Waveform};
+use engine::node::oversample::{Oversample, OversampleFactor};
+use engine::node::phaser::Phaser;
+use engine::node::reverb::Reverb;
+use engine::node::ring_modulator::{CarrierSource, ModulationMode, RingModulator};
+use engine::node::vocoder::Vocoder;
+use engine::node::Gain;
+use engine::node::{ramp::RampType, AudioNode, Dummy, InputType, MidiNode, OutputType, Ramp};
 use simple_error::SimpleError;
 
-use engine::constants::SAMPLE_RATE;
+use engine::constants::{SAMPLE_RATE, TWO_PI};
+use engine::midi::messages::MidiData;
+use engine::pipeline::midi_oscillator::MidiOscillator;
+use engine::util::tempo::{NoteDivision, NoteLength, NoteModifier};
 
 #[test]
 fn dummy_node_sets_output_correctly() -> Result<(), SimpleError> {
@@ -82,3 +104,852 @@ fn ramp_node_exponential_interpolate_works() -> Result<(), SimpleError> {
 
     Ok(())
 }
+
+#[test]
+fn oscillator_starts_at_start_phase() -> Result<(), SimpleError> {
+    // free running, and never gated
+    let mut osc = OscillatorNode::new(Waveform::Sine);
+    osc.set_start_phase(TWO_PI / 4.0);
+
+    osc.process();
+    assert!((osc.get_output_audio(OutputType::Out)? - 1.0).abs() < 0.001);
+
+    Ok(())
+}
+
+#[test]
+fn oscillator_key_sync_restarts_phase() -> Result<(), SimpleError> {
+    let mut osc = OscillatorNode::new(Waveform::Sine);
+    osc.set_phase_mode(PhaseMode::KeySync);
+    osc.set_start_phase(1.0);
+
+    osc.receive_audio(InputType::Gate, 1.0)?;
+    osc.process();
+    assert!((osc.get_phase() - 1.0).abs() < 0.0001);
+
+    // let it run to somewhere else, then play another note
+    osc.receive_audio(InputType::Gate, 0.0)?;
+    for _ in 0..1000 {
+        osc.process();
+    }
+
+    osc.receive_audio(InputType::Gate, 1.0)?;
+    osc.process();
+    assert!((osc.get_phase() - 1.0).abs() < 0.0001);
+
+    Ok(())
+}
+
+#[test]
+fn lfo_tempo_sync_and_delay() -> Result<(), SimpleError> {
+    let mut lfo = Lfo::new(LfoShape::Square, 1.0);
+    lfo.set_tempo(120.0);
+    lfo.set_rate(LfoRate::Synced(NoteLength::new(
+        NoteDivision::Quarter,
+        NoteModifier::Dotted,
+    )));
+    lfo.set_polarity(LfoPolarity::Unipolar);
+    lfo.set_delay(0.1);
+
+    // a dotted quarter at 120 bpm is 0.75 seconds long
+    assert!((lfo.get_frequency() - 1.0 / 0.75).abs() < 0.0001);
+
+    // silent during the delay
+    for _ in 0..(SAMPLE_RATE / 20) {
+        lfo.process();
+        assert!(lfo.get_output_audio(OutputType::Out)?.abs() < 0.0001);
+    }
+
+    // then between 0 and 1, reaching both ends
+    let mut min = 1.0_f32;
+    let mut max = 0.0_f32;
+    for _ in 0..SAMPLE_RATE {
+        lfo.process();
+        let value = lfo.get_output_audio(OutputType::Out)?;
+        min = min.min(value);
+        max = max.max(value);
+    }
+    assert!(min.abs() < 0.0001 && (max - 1.0).abs() < 0.0001);
+
+    Ok(())
+}
+
+#[test]
+fn lfo_phase_offset_moves_random_shapes() -> Result<(), SimpleError> {
+    // a quarter cycle ahead, so the first new value comes three quarters of the way through
+    let mut lfo = Lfo::new(LfoShape::SampleAndHold, 1.0);
+    lfo.set_phase_offset(TWO_PI / 4.0);
+
+    lfo.process();
+    let first = lfo.get_output_audio(OutputType::Out)?;
+
+    let mut changed_at = None;
+    for i in 1..SAMPLE_RATE {
+        lfo.process();
+
+        if changed_at.is_none() && lfo.get_output_audio(OutputType::Out)? != first {
+            changed_at = Some(i);
+        }
+    }
+    assert!(changed_at.unwrap().abs_diff(SAMPLE_RATE * 3 / 4) < SAMPLE_RATE / 1000);
+
+    // smooth random glides from the offset point too, so it starts halfway between 0 and its
+    // first value, and reaches that value half a cycle later
+    let mut smooth = Lfo::new(LfoShape::SmoothRandom, 1.0);
+    smooth.set_phase_offset(TWO_PI / 2.0);
+
+    smooth.process();
+    let first = smooth.get_output_audio(OutputType::Out)?;
+
+    for _ in 0..(SAMPLE_RATE / 2) {
+        smooth.process();
+    }
+    assert!((smooth.get_output_audio(OutputType::Out)? - first * 2.0).abs() < 0.001);
+
+    Ok(())
+}
+
+#[test]
+fn oversampled_node_passes_audio_through() -> Result<(), SimpleError> {
+    for factor in [OversampleFactor::X2, OversampleFactor::X4, OversampleFactor::X8] {
+        let mut gain = Gain::new();
+        gain.set_gain(1.0);
+
+        let mut oversampled = Oversample::new(gain, factor);
+        assert_eq!(oversampled.get_sample_rate(), SAMPLE_RATE * factor.multiplier() as u32);
+
+        let mut sum_of_squares = 0.0;
+        for i in 0..SAMPLE_RATE {
+            oversampled.receive_audio(InputType::In, (i as f32 / SAMPLE_RATE as f32 * 1_000.0 * TWO_PI).sin())?;
+            oversampled.process();
+
+            if i >= SAMPLE_RATE / 2 {
+                sum_of_squares += oversampled.get_output_audio(OutputType::Out)?.powi(2);
+            }
+        }
+
+        // same level as it went in
+        let amplitude = (sum_of_squares / (SAMPLE_RATE / 2) as f32 * 2.0).sqrt();
+        assert!((amplitude - 1.0).abs() < 0.01, "{:?}: {}", factor, amplitude);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn curved_envelope_keeps_exact_attack_time() -> Result<(), SimpleError> {
+    let attack_samples = (SAMPLE_RATE / 100) as usize;
+
+    let mut linear = Envelope::new(0.01, 0.1, 0.5, 0.1);
+    let mut curved = Envelope::new(0.01, 0.1, 0.5, 0.1);
+    curved.set_curves(
+        EnvelopeCurve::Exponential,
+        EnvelopeCurve::Exponential,
+        EnvelopeCurve::Exponential,
+    );
+
+    linear.receive_audio(InputType::Gate, 1.0)?;
+    curved.receive_audio(InputType::Gate, 1.0)?;
+
+    // the first sample switches into the attack
+    linear.process();
+    curved.process();
+
+    for i in 1..=attack_samples {
+        linear.process();
+        curved.process();
+
+        if i == attack_samples / 2 {
+            // an exponential attack rises faster at first
+            assert!(curved.get_output_audio(OutputType::Out)? > linear.get_output_audio(OutputType::Out)? + 0.2);
+        }
+    }
+
+    // but both reach the top at the same time
+    assert!((curved.get_output_audio(OutputType::Out)? - 1.0).abs() < 0.0001);
+    assert!((linear.get_output_audio(OutputType::Out)? - 1.0).abs() < 0.0001);
+
+    Ok(())
+}
+
+#[test]
+fn steep_custom_curves_stay_finite() -> Result<(), SimpleError> {
+    let attack_samples = (SAMPLE_RATE / 100) as usize;
+
+    for curvature in [-200.0, 200.0] {
+        let curve = EnvelopeCurve::Custom(curvature);
+
+        let mut envelope = Envelope::new(0.01, 0.1, 0.5, 0.1);
+        envelope.set_curves(curve, curve, curve);
+        envelope.receive_audio(InputType::Gate, 1.0)?;
+        envelope.process();
+
+        let mut last = envelope.get_output_audio(OutputType::Out)?;
+
+        for _ in 0..attack_samples {
+            envelope.process();
+            let level = envelope.get_output_audio(OutputType::Out)?;
+
+            assert!(level.is_finite());
+            assert!(level >= last);
+
+            last = level;
+        }
+
+        assert!((last - 1.0).abs() < 0.0001);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn multi_stage_envelope_loops_until_released() -> Result<(), SimpleError> {
+    // up in 10ms, down in 10ms, repeat, then a 10ms release
+    let mut envelope = MultiStageEnvelope::new(
+        vec![
+            Breakpoint::new(1.0, 0.01, EnvelopeCurve::Linear),
+            Breakpoint::new(0.5, 0.01, EnvelopeCurve::Linear),
+            Breakpoint::new(0.0, 0.01, EnvelopeCurve::Linear),
+        ],
+        None,
+    );
+    envelope.set_loop_points(Some((0, 1)));
+
+    let segment_samples = (SAMPLE_RATE / 100) as usize;
+
+    envelope.receive_audio(InputType::Gate, 1.0)?;
+    let mut peaks = 0;
+    for i in 0..(segment_samples * 10) {
+        envelope.process();
+
+        if (envelope.get_output_audio(OutputType::Out)? - 1.0).abs() < 0.0001 {
+            peaks += 1;
+        }
+
+        // never goes past the loop to the last breakpoint
+        if i > segment_samples {
+            assert!(envelope.get_output_audio(OutputType::Out)? >= 0.4999);
+        }
+    }
+    // once per loop
+    assert_eq!(peaks, 5);
+
+    envelope.receive_audio(InputType::Gate, 0.0)?;
+    for _ in 0..(segment_samples + 1) {
+        envelope.process();
+    }
+    assert!(envelope.get_output_audio(OutputType::Out)?.abs() < 0.0001);
+
+    Ok(())
+}
+
+#[test]
+fn dahdsr_envelope_holds_at_sustain() -> Result<(), SimpleError> {
+    let mut envelope = MultiStageEnvelope::dahdsr(0.01, 0.01, 0.01, 0.01, 0.25, 0.01);
+
+    envelope.receive_audio(InputType::Gate, 1.0)?;
+
+    // still delayed
+    for _ in 0..(SAMPLE_RATE / 200) {
+        envelope.process();
+    }
+    assert!(envelope.get_output_audio(OutputType::Out)?.abs() < 0.0001);
+
+    for _ in 0..(SAMPLE_RATE / 10) {
+        envelope.process();
+    }
+    assert!((envelope.get_output_audio(OutputType::Out)? - 0.25).abs() < 0.0001);
+
+    // release halfway, then play it again
+    envelope.receive_audio(InputType::Gate, 0.0)?;
+    for _ in 0..(SAMPLE_RATE / 200) {
+        envelope.process();
+    }
+    let released = envelope.get_output_audio(OutputType::Out)?;
+    assert!(released > 0.1 && released < 0.15);
+
+    // the delay holds where the release got to
+    envelope.receive_audio(InputType::Gate, 1.0)?;
+    for _ in 0..(SAMPLE_RATE / 200) {
+        envelope.process();
+        assert!((envelope.get_output_audio(OutputType::Out)? - released).abs() < 0.0001);
+    }
+
+    Ok(())
+}
+
+/// sends the same inputs a `MidiOscillator` sends its envelopes every sample, and returns the
+/// last output
+fn play_envelope(
+    envelope: &mut dyn EnvelopeGenerator,
+    gate: f32,
+    trigger: f32,
+    velocity: f32,
+    samples: u32,
+) -> Result<f32, SimpleError> {
+    let mut output = 0.0;
+
+    for i in 0..samples {
+        envelope.receive_audio(InputType::Gate, gate)?;
+        envelope.receive_audio(InputType::Trigger, if i == 0 { trigger } else { 0.0 })?;
+        envelope.receive_audio(InputType::Note, 64.0)?;
+        envelope.receive_audio(InputType::Velocity, velocity)?;
+        envelope.process();
+
+        output = envelope.get_output_audio(OutputType::Out)?;
+    }
+
+    Ok(output)
+}
+
+#[test]
+fn multi_stage_envelope_stands_in_for_envelope() -> Result<(), SimpleError> {
+    let mut adsr = Envelope::new(0.01, 0.01, 0.5, 0.01);
+    let mut multi_stage = MultiStageEnvelope::adsr(0.01, 0.01, 0.5, 0.01);
+    let envelopes: [&mut dyn EnvelopeGenerator; 2] = [&mut adsr, &mut multi_stage];
+
+    for envelope in envelopes {
+        assert!(envelope.is_finished());
+
+        // the same defaults, so velocity changes nothing yet
+        let sustained = play_envelope(envelope, 1.0, 1.0, 0.5, SAMPLE_RATE / 10)?;
+        assert!((sustained - 0.5).abs() < 0.0001);
+
+        // legato, so a new note while held carries on
+        let legato = play_envelope(envelope, 1.0, 1.0, 0.5, SAMPLE_RATE / 100)?;
+        assert!((legato - 0.5).abs() < 0.0001);
+
+        // retriggering, so it goes back up
+        envelope.set_trigger_mode(TriggerMode::Retrigger);
+        let retriggered = play_envelope(envelope, 1.0, 1.0, 1.0, SAMPLE_RATE / 200)?;
+        assert!(retriggered > 0.6);
+
+        let released = play_envelope(envelope, 0.0, 0.0, 1.0, SAMPLE_RATE / 10)?;
+        assert!(released.abs() < 0.0001);
+        assert!(envelope.is_finished());
+        assert!((envelope.get_output_audio(OutputType::Finished)? - 1.0).abs() < 0.0001);
+    }
+
+    // and scales by velocity, when asked to
+    let mut multi_stage = MultiStageEnvelope::adsr(0.01, 0.01, 0.5, 0.01);
+    multi_stage.set_velocity_sensitivity(1.0);
+    let sustained = play_envelope(&mut multi_stage, 1.0, 1.0, 0.5, SAMPLE_RATE / 10)?;
+    assert!((sustained - 0.25).abs() < 0.0001);
+
+    // a whole voice, shaped by multi stage envelopes
+    let mut voice = MidiOscillator::with_envelopes(
+        MultiStageEnvelope::adsr(0.001, 0.01, 0.5, 0.01),
+        MultiStageEnvelope::adsr(0.001, 0.01, 0.5, 0.01),
+    );
+
+    voice.receive_midi(InputType::In, &vec![MidiData::NoteOn { channel: 0, note: 69, velocity: 127 }])?;
+    voice.process();
+    voice.receive_midi(InputType::In, &vec![])?;
+
+    let mut loudest = 0_f32;
+    for _ in 0..(SAMPLE_RATE / 100) {
+        voice.process();
+        loudest = loudest.max(voice.get_output_audio(OutputType::Out)?.abs());
+    }
+    assert!(loudest > 0.4);
+
+    voice.receive_midi(InputType::In, &vec![MidiData::NoteOff { channel: 0, note: 69, velocity: 0 }])?;
+    for _ in 0..(SAMPLE_RATE / 10) {
+        voice.process();
+    }
+    assert!(voice.is_finished());
+    assert!((voice.get_output_audio(OutputType::Finished)? - 1.0).abs() < 0.0001);
+
+    Ok(())
+}
+
+#[test]
+fn envelope_scales_with_velocity_and_key() -> Result<(), SimpleError> {
+    let mut envelope = Envelope::new(0.01, 0.01, 1.0, 0.1);
+    envelope.set_velocity_sensitivity(1.0);
+    envelope.set_key_follow(1.0);
+
+    // a soft note an octave up
+    envelope.receive_audio(InputType::Velocity, 0.5)?;
+    envelope.receive_audio(InputType::Note, 72.0)?;
+    envelope.receive_audio(InputType::Gate, 1.0)?;
+    for _ in 0..(SAMPLE_RATE / 10) {
+        envelope.process();
+    }
+    assert!((envelope.get_output_audio(OutputType::Out)? - 0.5).abs() < 0.0001);
+
+    // release is halved to 0.05 seconds
+    envelope.receive_audio(InputType::Gate, 0.0)?;
+    for _ in 0..(SAMPLE_RATE / 40) {
+        envelope.process();
+    }
+    assert!((envelope.get_output_audio(OutputType::Out)? - 0.25).abs() < 0.001);
+
+    // a loud note picks up from where the release is, without jumping
+    envelope.receive_audio(InputType::Velocity, 1.0)?;
+    envelope.receive_audio(InputType::Gate, 1.0)?;
+    envelope.process();
+    assert!((envelope.get_output_audio(OutputType::Out)? - 0.25).abs() < 0.01);
+
+    Ok(())
+}
+
+#[test]
+fn envelope_retriggers_and_finishes() -> Result<(), SimpleError> {
+    let mut legato = Envelope::new(0.01, 0.01, 0.5, 0.01);
+    let mut retrigger = Envelope::new(0.01, 0.01, 0.5, 0.01);
+    retrigger.set_trigger_mode(TriggerMode::Retrigger);
+    retrigger.set_retrigger_start(RetriggerStart::Reset);
+
+    for envelope in [&mut legato, &mut retrigger] {
+        envelope.receive_audio(InputType::Gate, 1.0)?;
+        envelope.receive_audio(InputType::Trigger, 1.0)?;
+        envelope.process();
+        envelope.receive_audio(InputType::Trigger, 0.0)?;
+
+        for _ in 0..(SAMPLE_RATE / 10) {
+            envelope.process();
+        }
+        assert_eq!(envelope.get_state(), EnvelopeState::Sustaining);
+
+        // a second, overlapping note
+        envelope.receive_audio(InputType::Trigger, 1.0)?;
+        envelope.process();
+        envelope.receive_audio(InputType::Trigger, 0.0)?;
+    }
+
+    assert_eq!(legato.get_state(), EnvelopeState::Sustaining);
+    assert_eq!(retrigger.get_state(), EnvelopeState::Attacking);
+    assert!(retrigger.get_output_audio(OutputType::Out)? < 0.01);
+
+    retrigger.receive_audio(InputType::Gate, 0.0)?;
+    retrigger.process();
+    assert_eq!(retrigger.get_output_audio(OutputType::Finished)?, 0.0);
+
+    for _ in 0..(SAMPLE_RATE / 50) {
+        retrigger.process();
+    }
+    assert!(retrigger.is_finished());
+    assert_eq!(retrigger.get_output_audio(OutputType::Finished)?, 1.0);
+
+    Ok(())
+}
+
+#[test]
+fn midi_oscillator_holds_through_overlapping_notes() -> Result<(), SimpleError> {
+    let mut voice = MidiOscillator::new();
+    voice.set_adsr(0.001, 0.001, 0.5, 0.01);
+    voice.set_trigger_mode(TriggerMode::Legato);
+
+    let play = |voice: &mut MidiOscillator, messages: Vec<MidiData>| -> Result<(), SimpleError> {
+        voice.receive_midi(InputType::In, &messages)?;
+        voice.process();
+        voice.receive_midi(InputType::In, &vec![])?;
+
+        for _ in 0..(SAMPLE_RATE / 100) {
+            voice.process();
+        }
+
+        Ok(())
+    };
+
+    play(&mut voice, vec![MidiData::NoteOn { channel: 0, note: 60, velocity: 127 }])?;
+    play(&mut voice, vec![MidiData::NoteOn { channel: 0, note: 62, velocity: 127 }])?;
+    assert_eq!(voice.get_envelope_state(), EnvelopeState::Sustaining);
+
+    // letting go of a note that isn't playing any more leaves the gate alone
+    play(&mut voice, vec![MidiData::NoteOff { channel: 0, note: 60, velocity: 0 }])?;
+    assert!(voice.get_gate());
+    assert_eq!(voice.get_envelope_state(), EnvelopeState::Sustaining);
+
+    // letting go of 64 while 62 is held goes back to 62, still without releasing
+    play(&mut voice, vec![MidiData::NoteOn { channel: 0, note: 64, velocity: 127 }])?;
+    play(&mut voice, vec![MidiData::NoteOff { channel: 0, note: 64, velocity: 0 }])?;
+    assert!(voice.get_gate());
+    assert_eq!(voice.get_envelope_state(), EnvelopeState::Sustaining);
+
+    play(&mut voice, vec![MidiData::NoteOff { channel: 0, note: 62, velocity: 0 }])?;
+    assert!(!voice.get_gate());
+    assert!(voice.is_finished());
+
+    Ok(())
+}
+
+#[test]
+fn envelope_follower_tracks_level() -> Result<(), SimpleError> {
+    let mut peak = EnvelopeFollower::new(0.001, 0.05);
+    let mut rms = EnvelopeFollower::new(0.001, 0.05);
+    rms.set_detection(Detection::Rms);
+    let mut decibels = EnvelopeFollower::new(0.001, 0.05);
+    decibels.set_scale(FollowerScale::Decibels);
+
+    // a 500 hz sine at half scale
+    for i in 0..(SAMPLE_RATE / 10) {
+        let sample = 0.5 * f32::sin(TWO_PI * 500.0 * i as f32 / SAMPLE_RATE as f32);
+
+        for follower in [&mut peak, &mut rms, &mut decibels] {
+            follower.receive_audio(InputType::In, sample)?;
+            follower.process();
+        }
+    }
+
+    // peak detection rides just under the peaks, rms settles at the average power
+    let peak_level = peak.get_output_audio(OutputType::Out)?;
+    assert!(peak_level > 0.4 && peak_level <= 0.5, "{}", peak_level);
+    assert!((rms.get_output_audio(OutputType::Out)? - 0.5 / f32::sqrt(2.0)).abs() < 0.03);
+    assert!((decibels.get_output_audio(OutputType::Out)? - 20.0 * peak_level.log10()).abs() < 0.0001);
+
+    // silence lets it fall away at the release time
+    for _ in 0..(SAMPLE_RATE / 2) {
+        peak.receive_audio(InputType::In, 0.0)?;
+        peak.process();
+    }
+    assert!(peak.get_output_audio(OutputType::Out)? < 0.001);
+
+    Ok(())
+}
+
+#[test]
+fn delay_echoes_and_ping_pongs() -> Result<(), SimpleError> {
+    let delay_samples = (SAMPLE_RATE / 100) as usize;
+
+    for ping_pong in [false, true] {
+        let mut delay = Delay::new(DelayTime::Seconds(0.01), 0.5);
+        delay.set_mix(1.0);
+        delay.set_ping_pong(ping_pong);
+
+        let mut left = vec![];
+        let mut right = vec![];
+        for i in 0..(delay_samples * 3) {
+            delay.receive_audio(InputType::In, if i == 0 { 1.0 } else { 0.0 })?;
+            delay.process();
+            left.push(delay.get_output_audio(OutputType::Left)?);
+            right.push(delay.get_output_audio(OutputType::Right)?);
+        }
+
+        // the second echo has been through the feedback filter, which spreads it out a bit
+        let second_echo = |side: &Vec<f32>| -> f32 {
+            side[(delay_samples * 2 - 4)..(delay_samples * 2 + 16)].iter().sum()
+        };
+
+        // the impulse comes back after the delay time, then again quieter
+        assert!(left[delay_samples - 1].abs() < 0.01);
+        assert!((left[delay_samples] - 1.0).abs() < 0.01);
+
+        if ping_pong {
+            assert!(right[delay_samples].abs() < 0.01);
+            assert!(second_echo(&left).abs() < 0.01);
+            assert!((second_echo(&right) - 0.5).abs() < 0.01);
+        } else {
+            assert!((second_echo(&left) - 0.5).abs() < 0.01);
+            assert_eq!(left, right);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn reverb_tail_follows_decay_and_width() -> Result<(), SimpleError> {
+    // energy in each 100ms window of the impulse response
+    let tail = |reverb: &mut Reverb| -> Result<Vec<f32>, SimpleError> {
+        let mut windows = vec![0.0; 10];
+
+        for i in 0..(SAMPLE_RATE as usize) {
+            reverb.receive_audio(InputType::In, if i == 0 { 1.0 } else { 0.0 })?;
+            reverb.process();
+            windows[i / (SAMPLE_RATE as usize / 10)] += reverb.get_output_audio(OutputType::Out)?.powi(2);
+        }
+
+        Ok(windows)
+    };
+
+    let mut short = Reverb::new(0.5, 0.3);
+    short.set_mix(1.0);
+    let mut long = Reverb::new(0.5, 3.0);
+    long.set_mix(1.0);
+
+    let short_tail = tail(&mut short)?;
+    let long_tail = tail(&mut long)?;
+
+    // a 0.3 second decay has dropped way more than 60 db by the end
+    assert!(short_tail[9] < short_tail[1] * 1e-6);
+    // a 3 second one has only dropped around 20 db
+    assert!(long_tail[9] > long_tail[1] * 1e-3);
+
+    // no width, no difference between the sides
+    let mut mono = Reverb::new(0.5, 1.0);
+    mono.set_width(0.0);
+    for i in 0..(SAMPLE_RATE / 10) {
+        mono.receive_audio(InputType::In, if i == 0 { 1.0 } else { 0.0 })?;
+        mono.process();
+        assert_eq!(mono.get_output_audio(OutputType::Left)?, mono.get_output_audio(OutputType::Right)?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn modulation_effects_delay_and_cancel() -> Result<(), SimpleError> {
+    // with no depth the chorus and flanger are plain delays
+    let mut chorus = Chorus::new(1.0, 0.0);
+    chorus.set_mix(1.0);
+    let mut flanger = Flanger::new(1.0, 0.0);
+    flanger.set_feedback(0.0);
+    flanger.set_mix(1.0);
+
+    let mut chorus_out = vec![];
+    let mut flanger_out = vec![];
+    for i in 0..(SAMPLE_RATE / 50) {
+        let impulse = if i == 0 { 1.0 } else { 0.0 };
+
+        chorus.receive_audio(InputType::In, impulse)?;
+        chorus.process();
+        chorus_out.push(chorus.get_output_audio(OutputType::Out)?);
+
+        flanger.receive_audio(InputType::In, impulse)?;
+        flanger.process();
+        flanger_out.push(flanger.get_output_audio(OutputType::Out)?);
+    }
+    assert!((chorus_out[(SAMPLE_RATE as f32 * 0.015) as usize] - 1.0).abs() < 0.001);
+    assert!((flanger_out[(SAMPLE_RATE as f32 * 0.0005) as usize] - 1.0).abs() < 0.001);
+
+    // one allpass stage turns its center frequency upside down, cancelling the dry signal
+    let mut phaser = Phaser::new(1.0, 0.0);
+    phaser.set_stages(1);
+
+    let mut sum_of_squares = 0.0;
+    for i in 0..(SAMPLE_RATE / 5) {
+        phaser.receive_audio(InputType::In, f32::sin(TWO_PI * 800.0 * i as f32 / SAMPLE_RATE as f32))?;
+        phaser.process();
+
+        if i >= SAMPLE_RATE / 10 {
+            sum_of_squares += phaser.get_output_audio(OutputType::Out)?.powi(2);
+        }
+    }
+    assert!(sum_of_squares / ((SAMPLE_RATE / 10) as f32) < 0.0001);
+
+    Ok(())
+}
+
+#[test]
+fn distortion_curves_and_oversampling() -> Result<(), SimpleError> {
+    assert!((ShaperCurve::Foldback.apply(1.5) - 0.5).abs() < 0.0001);
+    assert!((ShaperCurve::Tube.apply(-10.0) + 0.5).abs() < 0.0001);
+    assert!((ShaperCurve::Table(vec![-1.0, 0.0, 0.5]).apply(0.5) - 0.25).abs() < 0.0001);
+
+    // a hard clipped 7khz sine has harmonics at 35khz and 49khz, which alias to 13khz and 1khz
+    let aliasing = |distortion: &mut Distortion| -> Result<f32, SimpleError> {
+        let mut bins = [(0.0, 0.0); 2];
+
+        for i in 0..(SAMPLE_RATE as usize / 5) {
+            let time = i as f32 / SAMPLE_RATE as f32;
+            distortion.receive_audio(InputType::In, f32::sin(TWO_PI * 7000.0 * time))?;
+            distortion.process();
+
+            // skip the filters settling
+            if i >= SAMPLE_RATE as usize / 10 {
+                let output = distortion.get_output_audio(OutputType::Out)?;
+
+                for (bin, frequency) in bins.iter_mut().zip([1000.0, 13000.0]) {
+                    bin.0 += output * f32::cos(TWO_PI * frequency * time);
+                    bin.1 += output * f32::sin(TWO_PI * frequency * time);
+                }
+            }
+        }
+
+        Ok(bins.iter().map(|(real, imaginary)| f32::sqrt(real * real + imaginary * imaginary)).sum())
+    };
+
+    let mut plain = Distortion::new(ShaperCurve::HardClip, 12.0);
+    plain.set_tone(20000.0);
+    let mut oversampled = Distortion::new(ShaperCurve::HardClip, 12.0);
+    oversampled.set_tone(20000.0);
+    oversampled.set_oversampling(Some(OversampleFactor::X4));
+
+    assert!(aliasing(&mut oversampled)? < aliasing(&mut plain)? * 0.25);
+
+    Ok(())
+}
+
+#[test]
+fn compressor_and_limiter_reduce_gain() -> Result<(), SimpleError> {
+    let mut compressor = Compressor::new(-20.0, 4.0);
+    compressor.set_knee(0.0);
+
+    // 0 db is 20 db over, so it comes out 15 db down
+    assert!((compressor.static_gain_reduction(0.0) - 15.0).abs() < 0.0001);
+    assert_eq!(compressor.static_gain_reduction(-30.0), 0.0);
+
+    // a loud sidechain turns down quiet audio
+    compressor.set_sidechain(true);
+    for _ in 0..(SAMPLE_RATE / 10) {
+        compressor.receive_audio(InputType::In, 0.01)?;
+        compressor.receive_audio(InputType::Sidechain, 1.0)?;
+        compressor.process();
+    }
+    assert!((compressor.get_output_audio(OutputType::GainReduction)? - 15.0).abs() < 0.01);
+    assert!((compressor.get_output_audio(OutputType::Out)? - 0.01 * f32::powf(10.0, -15.0 / 20.0)).abs() < 0.0001);
+
+    // nothing gets past the limiter, even a sudden jump
+    let mut limiter = Limiter::new(-1.0);
+    let ceiling = f32::powf(10.0, -1.0 / 20.0);
+    let mut loudest: f32 = 0.0;
+    for i in 0..(SAMPLE_RATE / 10) {
+        let input = if i < SAMPLE_RATE / 20 { 0.1 } else { 4.0 } * f32::sin(TWO_PI * 440.0 * i as f32 / SAMPLE_RATE as f32);
+        limiter.receive_audio(InputType::In, input)?;
+        limiter.process();
+        loudest = loudest.max(limiter.get_output_audio(OutputType::Out)?.abs());
+    }
+    assert!(loudest <= ceiling);
+    assert!(loudest > ceiling * 0.9);
+
+    Ok(())
+}
+
+#[test]
+fn bitcrusher_quantizes_and_holds() -> Result<(), SimpleError> {
+    let mut bitcrusher = Bitcrusher::new(3.0, SAMPLE_RATE as f32 / 4.0);
+
+    let mut outputs = vec![];
+    for i in 0..16 {
+        bitcrusher.receive_audio(InputType::In, i as f32 / 16.0)?;
+        bitcrusher.process();
+        outputs.push(bitcrusher.get_output_audio(OutputType::Out)?);
+    }
+
+    // 3 bits is steps of a quarter, each held for 4 samples
+    assert_eq!(outputs[0..4], [0.0; 4]);
+    assert_eq!(outputs[4..8], [0.25; 4]);
+    assert_eq!(outputs[12..16], [0.75; 4]);
+
+    // an octave down holds for 8, a bit less is steps of a half
+    bitcrusher.receive_audio(InputType::RateOffset, -1.0)?;
+    bitcrusher.receive_audio(InputType::BitsOffset, -1.0)?;
+
+    let mut outputs = vec![];
+    for i in 0..16 {
+        bitcrusher.receive_audio(InputType::In, if i == 0 { 0.3 } else { 0.9 })?;
+        bitcrusher.process();
+        outputs.push(bitcrusher.get_output_audio(OutputType::Out)?);
+    }
+    assert_eq!(outputs[0..8], [0.5; 8]);
+    assert_eq!(outputs[8..16], [1.0; 8]);
+
+    Ok(())
+}
+
+#[test]
+fn ring_modulator_multiplies_inputs() -> Result<(), SimpleError> {
+    let mut ring = RingModulator::new(CarrierSource::External);
+
+    ring.receive_audio(InputType::Modulator, 0.5)?;
+    ring.receive_audio(InputType::Carrier, -0.5)?;
+    ring.process();
+    assert_eq!(ring.get_output_audio(OutputType::Out)?, -0.25);
+
+    // amplitude modulation lifts the carrier, half depth only goes halfway
+    ring.set_mode(ModulationMode::Amplitude);
+    ring.set_depth(0.5);
+    ring.process();
+    assert_eq!(ring.get_output_audio(OutputType::Out)?, 0.5 * (0.5 + 0.5 * 0.25));
+
+    // the internal carrier ignores the carrier input
+    let mut internal = RingModulator::new(CarrierSource::Internal);
+    internal.set_frequency(1_000.0);
+    let mut loudest: f32 = 0.0;
+    for _ in 0..(SAMPLE_RATE / 100) {
+        internal.receive_audio(InputType::Modulator, 1.0)?;
+        internal.receive_audio(InputType::Carrier, 0.0)?;
+        internal.process();
+        loudest = loudest.max(internal.get_output_audio(OutputType::Out)?);
+    }
+    assert!(loudest > 0.99);
+
+    Ok(())
+}
+
+#[test]
+fn vocoder_follows_modulator_spectrum() -> Result<(), SimpleError> {
+    // level of the 200hz and 1khz harmonics of a 100hz impulse train carrier (which has all its
+    // harmonics at the same level), with a 1khz modulator
+    let harmonics = |vocoder: &mut Vocoder, modulator_level: f32| -> Result<[f32; 2], SimpleError> {
+        let mut bins = [(0.0, 0.0); 2];
+
+        for i in 0..(SAMPLE_RATE as usize / 2) {
+            let time = i as f32 / SAMPLE_RATE as f32;
+
+            vocoder.receive_audio(InputType::Carrier, if i % 480 == 0 { 1.0 } else { 0.0 })?;
+            vocoder.receive_audio(InputType::Modulator, modulator_level * f32::sin(TWO_PI * 1000.0 * time))?;
+            vocoder.process();
+
+            if i >= SAMPLE_RATE as usize / 10 {
+                let output = vocoder.get_output_audio(OutputType::Out)?;
+
+                for (bin, frequency) in bins.iter_mut().zip([200.0, 1000.0]) {
+                    bin.0 += output * f32::cos(TWO_PI * frequency * time);
+                    bin.1 += output * f32::sin(TWO_PI * frequency * time);
+                }
+            }
+        }
+
+        let samples = (SAMPLE_RATE as f32 * 0.4) / 2.0;
+        Ok(bins.map(|(real, imaginary)| f32::sqrt(real * real + imaginary * imaginary) / samples))
+    };
+
+    let mut previous_ratio = 0.0;
+    for bands in [8, 16, 32] {
+        let mut vocoder = Vocoder::new(bands);
+        let [low, high] = harmonics(&mut vocoder, 1.0)?;
+
+        // mostly the carrier harmonic where the modulator is comes through, more so with more bands
+        let ratio = high / low;
+        assert!(ratio > 2.0 && ratio > previous_ratio, "{} bands: {} and {}", bands, low, high);
+        previous_ratio = ratio;
+    }
+
+    // nothing to follow, nothing out
+    let mut vocoder = Vocoder::new(16);
+    let [low, high] = harmonics(&mut vocoder, 0.0)?;
+    assert!(low < 0.0001 && high < 0.0001);
+
+    Ok(())
+}
+
+#[test]
+fn mixer_pans_solos_and_sends() -> Result<(), SimpleError> {
+    let mut mixer = Mixer::new(3, 1);
+    mixer.get_channel_mut(0).unwrap().pan = -1.0;
+    mixer.get_channel_mut(1).unwrap().level = 0.5;
+    mixer.get_channel_mut(2).unwrap().sends[0] = 0.25;
+
+    for channel in 0..3 {
+        mixer.receive_audio(InputType::Channel(channel), 1.0)?;
+    }
+    assert!(mixer.receive_audio(InputType::Channel(3), 1.0).is_err());
+
+    mixer.process();
+
+    // the hard left channel only goes left, the centered ones are 3db down each side
+    let center = f32::sqrt(0.5);
+    assert_eq!(mixer.get_output_audio(OutputType::Out)?, 2.5);
+    assert!((mixer.get_output_audio(OutputType::Left)? - (1.0 + 1.5 * center)).abs() < 0.0001);
+    assert!((mixer.get_output_audio(OutputType::Right)? - 1.5 * center).abs() < 0.0001);
+    assert_eq!(mixer.get_output_audio(OutputType::Aux(0))?, 0.25);
+
+    mixer.set_pan_law(PanLaw::Linear);
+    mixer.process();
+    assert!((mixer.get_output_audio(OutputType::Right)? - 0.75).abs() < 0.0001);
+
+    // soloing the first leaves only it, unless it's muted too
+    mixer.get_channel_mut(0).unwrap().solo = true;
+    mixer.process();
+    assert_eq!(mixer.get_output_audio(OutputType::Out)?, 1.0);
+    assert_eq!(mixer.get_output_audio(OutputType::Aux(0))?, 0.0);
+
+    mixer.get_channel_mut(0).unwrap().mute = true;
+    mixer.process();
+    assert_eq!(mixer.get_output_audio(OutputType::Out)?, 0.0);
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use engine::constants::TWO_PI;
+use engine::wave::interpolate::{interpolate, Interpolation};
+use engine::wave::tables::{BASE_FREQUENCY, FREQUENCY_STEPS, SAWTOOTH_VALUES, SINE_VALUES};
+
+#[test]
+fn interpolation_modes_follow_sine() {
+    for quality in [Interpolation::Linear, Interpolation::Cubic, Interpolation::Sinc] {
+        for i in 0..100 {
+            let phase = i as f32 / 100.0 * TWO_PI + 0.0123;
+            let sample = interpolate(&SINE_VALUES, 440.0, phase, quality);
+
+            assert!((sample - phase.sin()).abs() < 0.001, "{:?} off at {}", quality, phase);
+        }
+    }
+}
+
+#[test]
+fn interpolation_handles_out_of_range_frequencies() {
+    let highest = BASE_FREQUENCY * FREQUENCY_STEPS as f32;
+
+    for frequency in [-440.0, -highest * 2.0, highest - 1.0, highest, highest * 2.0] {
+        let sample = interpolate(&SAWTOOTH_VALUES, frequency, -1.0, Interpolation::Cubic);
+
+        assert!(sample.is_finite());
+    }
+
+    // negative frequencies use the same tables as positive ones
+    let negative = interpolate(&SAWTOOTH_VALUES, -440.0, 1.0, Interpolation::Linear);
+    let positive = interpolate(&SAWTOOTH_VALUES, 440.0, 1.0, Interpolation::Linear);
+    assert!((negative - positive).abs() < 0.0001);
+}
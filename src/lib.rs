@@ -3,7 +3,9 @@ use simple_error::SimpleError;
 use engine::node::envelope::Envelope;
 use engine::node::filter::{Filter, FilterType};
 use engine::node::gain::Gain;
-use engine::node::oscillator::{Oscillator, OscillatorNode, Waveform};
+use engine::node::lfo::{Lfo, LfoShape};
+use engine::node::limiter::Limiter;
+use engine::node::oscillator::Waveform;
 use engine::pipeline::midi_oscillator::MidiOscillator;
 use engine::node::ramp::{Ramp, RampType};
 use engine::node::{InputType, AudioNode, MidiNode, OutputType};   
@@ -13,10 +15,11 @@ use engine::midi::messages::MidiData;
 pub struct OneSampleData {
     envelope: Envelope,
     osc: MidiOscillator,
-    lfo: OscillatorNode,
+    lfo: Lfo,
     filter: Filter,
     gain: Gain,
     ramp: Ramp,
+    limiter: Limiter,
     notes_on: i32,
 }
 
@@ -28,6 +31,7 @@ pub fn init() -> OneSampleData {
         filter: create_test_filter(),
         gain: create_test_gain(),
         ramp: create_test_ramp(),
+        limiter: create_test_limiter(),
         notes_on: 0
     }
 }
@@ -51,9 +55,13 @@ pub fn one_sample(state: &mut OneSampleData, midi: &mut Vec<MidiData>, _sample_i
     )?;
     state.filter.process();
 
+    // keep the master bus from clipping
+    state.limiter.receive_audio(InputType::In, state.gain.get_output_audio(OutputType::Out)?)?;
+    state.limiter.process();
+
     midi.clear();
 
-    state.gain.get_output_audio(OutputType::Out)
+    state.limiter.get_output_audio(OutputType::Out)
 }
 
 
@@ -68,11 +76,8 @@ fn create_test_oscillator() -> MidiOscillator {
     osc
 }
 
-fn create_test_lfo() -> OscillatorNode {
-    let mut osc = OscillatorNode::new(Waveform::Sine);
-    osc.set_frequency(1.0);
-
-    osc
+fn create_test_lfo() -> Lfo {
+    Lfo::new(LfoShape::Sine, 1.0)
 }
 
 fn create_test_filter() -> Filter {
@@ -92,3 +97,6 @@ fn create_test_ramp() -> Ramp {
     ramp
 }
 
+fn create_test_limiter() -> Limiter {
+    Limiter::new(-0.3)
+}